@@ -0,0 +1,478 @@
+use std::fmt;
+
+use super::{Namespace, Var, Enum, Variant, TypeAlias, Class, Base, ClassTemplate, Field, Function, Access, Value, ItemKind};
+
+
+
+/// A single difference between two API trees.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Change {
+	/// Qualified path of the item, outermost first.
+	pub path: Vec<String>,
+	pub item: ItemKind,
+	pub kind: ChangeKind,
+	/// What changed, or the signature of an added or removed overload.
+	pub detail: Option<String>,
+	/// Whether bindings generated from the old API may no longer work
+	/// against the new one.
+	pub breaking: bool,
+}
+
+
+
+#[derive(Copy, Clone, PartialEq, Eq, Debug, Serialize, Deserialize)]
+pub enum ChangeKind {
+	Added,
+	Removed,
+	Changed,
+}
+
+
+
+/// Lists the changes from `old` to `new`, in the order they are found in
+/// the old tree followed by additions. Private members are compared but
+/// never count as breaking, since bindings only see them as padding.
+pub fn diff(old: &Namespace, new: &Namespace) -> Vec<Change> {
+	let mut differ = Differ{path: Vec::new(), changes: Vec::new()};
+	old.compare(new, &mut differ);
+	differ.changes
+}
+
+
+
+struct Differ {
+	path: Vec<String>,
+	changes: Vec<Change>,
+}
+
+
+
+impl Differ {
+	fn push(&mut self, item: ItemKind, kind: ChangeKind, name: Option<&str>, detail: Option<String>, breaking: bool) {
+		let mut path = self.path.clone();
+		if let Some(name) = name {
+			path.push(name.to_string());
+		}
+
+		self.changes.push(Change{
+			path: path,
+			item: item,
+			kind: kind,
+			detail: detail,
+			breaking: breaking,
+		});
+	}
+
+
+
+	/// Records a change to the item at the current path.
+	fn changed(&mut self, item: ItemKind, detail: String, breaking: bool) {
+		self.push(item, ChangeKind::Changed, None, Some(detail), breaking);
+	}
+
+
+
+	/// Matches items by name. Unnamed items, such as padding fields, are
+	/// skipped; their effect shows up in the layout of the enclosing class.
+	fn diff_items<T: Item>(&mut self, old: &[T], new: &[T]) {
+		for o in old.iter() {
+			let name = o.name();
+			if name.is_empty() {
+				continue;
+			}
+
+			match new.iter().find(|n| n.name() == name) {
+				Some(n) => {
+					self.path.push(name);
+					o.compare(n, self);
+					self.path.pop();
+				},
+				None => self.push(T::kind(), ChangeKind::Removed, Some(name.as_str()), None, o.is_exposed()),
+			}
+		}
+		for n in new.iter() {
+			let name = n.name();
+			if !name.is_empty() && !old.iter().any(|o| o.name() == name) {
+				self.push(T::kind(), ChangeKind::Added, Some(name.as_str()), None, false);
+			}
+		}
+	}
+
+
+
+	/// Matches functions by signature. When exactly one overload of a name
+	/// disappears and exactly one appears, it is reported as a signature
+	/// change rather than a removal and an addition.
+	fn diff_functions(&mut self, item: ItemKind, old: &[Function], new: &[Function]) {
+		let unmatched = |fs: &[Function], others: &[Function], name: &str| {
+			fs.iter().filter(|f| f.name == name && !others.iter().any(|o| o.same_signature(f))).count()
+		};
+		let mut added: Vec<&Function> = new.iter().filter(|n| !old.iter().any(|o| o.same_signature(n))).collect();
+
+		for o in old.iter() {
+			if let Some(n) = new.iter().find(|n| n.same_signature(o)) {
+				self.path.push(o.name.clone());
+				if o.return_ty != n.return_ty {
+					self.changed(item, format!("{} -> {}", signature(o), signature(n)), o.is_exposed());
+				}
+				if o.access != n.access {
+					self.changed(item, format!("access {:?} -> {:?}", o.access, n.access), o.is_exposed() && narrows(o.access, n.access));
+				}
+				self.path.pop();
+				continue;
+			}
+
+			if unmatched(old, new, &o.name) == 1 && unmatched(new, old, &o.name) == 1 {
+				let i = added.iter().position(|n| n.name == o.name).unwrap();
+				let n = added.remove(i);
+				self.push(item, ChangeKind::Changed, Some(o.name.as_str()), Some(format!("{} -> {}", signature(o), signature(n))), o.is_exposed());
+			} else {
+				self.push(item, ChangeKind::Removed, Some(o.name.as_str()), Some(signature(o)), o.is_exposed());
+			}
+		}
+		for n in added.into_iter() {
+			self.push(item, ChangeKind::Added, Some(n.name.as_str()), Some(signature(n)), false);
+		}
+	}
+}
+
+
+
+trait Item {
+	fn kind() -> ItemKind;
+	fn name(&self) -> String;
+	fn is_exposed(&self) -> bool { true }
+	/// Records the changes from `self` to `new`, which has the same name.
+	fn compare(&self, new: &Self, d: &mut Differ);
+}
+
+
+
+impl Item for Namespace {
+	fn kind() -> ItemKind { ItemKind::Namespace }
+	fn name(&self) -> String { self.name.clone() }
+
+	fn compare(&self, new: &Namespace, d: &mut Differ) {
+		d.diff_items(&self.globals, &new.globals);
+		d.diff_items(&self.enums, &new.enums);
+		d.diff_items(&self.aliases, &new.aliases);
+		d.diff_functions(ItemKind::Function, &self.functions, &new.functions);
+		d.diff_items(&self.classes, &new.classes);
+		d.diff_items(&self.templates, &new.templates);
+		d.diff_items(&self.namespaces, &new.namespaces);
+	}
+}
+
+
+
+impl Item for Var {
+	fn kind() -> ItemKind { ItemKind::Global }
+	fn name(&self) -> String { self.name.clone() }
+
+	fn compare(&self, new: &Var, d: &mut Differ) {
+		if !self.ty.same_type(&new.ty) {
+			d.changed(ItemKind::Global, format!("type {} -> {}", self.ty, new.ty), true);
+		} else if self.ty.value != new.ty.value {
+			d.changed(ItemKind::Global, format!("value {} -> {}", display_value(&self.ty.value), display_value(&new.ty.value)), false);
+		}
+	}
+}
+
+
+
+impl Item for Enum {
+	fn kind() -> ItemKind { ItemKind::Enum }
+	fn name(&self) -> String { self.name.clone() }
+
+	fn compare(&self, new: &Enum, d: &mut Differ) {
+		if self.underlying != new.underlying {
+			d.changed(ItemKind::Enum, format!("underlying type {:?} -> {:?}", self.underlying, new.underlying), true);
+		}
+		d.diff_items(&self.variants, &new.variants);
+	}
+}
+
+
+
+impl Item for Variant {
+	fn kind() -> ItemKind { ItemKind::Variant }
+	fn name(&self) -> String { self.name.clone() }
+
+	fn compare(&self, new: &Variant, d: &mut Differ) {
+		if self.value != new.value {
+			d.changed(ItemKind::Variant, format!("value {} -> {}", self.value, new.value), true);
+		}
+	}
+}
+
+
+
+impl Item for TypeAlias {
+	fn kind() -> ItemKind { ItemKind::Alias }
+	fn name(&self) -> String { self.name.to_string() }
+
+	fn compare(&self, new: &TypeAlias, d: &mut Differ) {
+		if !self.ty.same_type(&new.ty) {
+			d.changed(ItemKind::Alias, format!("{} -> {}", self.ty, new.ty), true);
+		}
+	}
+}
+
+
+
+impl Item for Class {
+	fn kind() -> ItemKind { ItemKind::Class }
+	fn name(&self) -> String { self.name.to_string() }
+
+	fn compare(&self, new: &Class, d: &mut Differ) {
+		if self.size != new.size {
+			d.changed(ItemKind::Class, format!("size {} -> {}", display_layout(self.size), display_layout(new.size)), true);
+		}
+		if self.align != new.align {
+			d.changed(ItemKind::Class, format!("alignment {} -> {}", display_layout(self.align), display_layout(new.align)), true);
+		}
+		if self.is_pod != new.is_pod {
+			d.changed(ItemKind::Class, format!("POD {} -> {}", self.is_pod, new.is_pod), true);
+		}
+		if self.is_union != new.is_union {
+			d.changed(ItemKind::Class, format!("union {} -> {}", self.is_union, new.is_union), true);
+		}
+		if self.virtual_dtor != new.virtual_dtor {
+			d.changed(ItemKind::Class, format!("virtual destructor {} -> {}", self.virtual_dtor, new.virtual_dtor), true);
+		}
+
+		d.diff_items(&self.bases, &new.bases);
+		d.diff_items(&self.enums, &new.enums);
+		d.diff_items(&self.aliases, &new.aliases);
+		d.diff_items(&self.fields, &new.fields);
+
+		// Anonymous unions can only be matched up by position
+		for (i, (o, n)) in self.anon_unions.iter().zip(new.anon_unions.iter()).enumerate() {
+			d.path.push(format!("(anonymous union {})", i));
+			o.compare(n, d);
+			d.path.pop();
+		}
+		if self.anon_unions.len() != new.anon_unions.len() {
+			d.changed(ItemKind::Class, format!("anonymous unions {} -> {}", self.anon_unions.len(), new.anon_unions.len()), true);
+		}
+
+		d.diff_functions(ItemKind::Constructor, &self.ctors, &new.ctors);
+		d.diff_functions(ItemKind::Method, &self.methods, &new.methods);
+		d.diff_items(&self.classes, &new.classes);
+	}
+}
+
+
+
+impl Item for Base {
+	fn kind() -> ItemKind { ItemKind::Base }
+	fn name(&self) -> String { self.ty.to_string() }
+	fn is_exposed(&self) -> bool { self.access != Access::Private }
+
+	fn compare(&self, new: &Base, d: &mut Differ) {
+		if self.access != new.access {
+			d.changed(ItemKind::Base, format!("access {:?} -> {:?}", self.access, new.access), self.is_exposed() && narrows(self.access, new.access));
+		}
+		if self.is_virtual != new.is_virtual {
+			d.changed(ItemKind::Base, format!("virtual {} -> {}", self.is_virtual, new.is_virtual), true);
+		}
+		if self.offset != new.offset {
+			d.changed(ItemKind::Base, format!("bit offset {} -> {}", display_layout(self.offset), display_layout(new.offset)), true);
+		}
+	}
+}
+
+
+
+impl Item for ClassTemplate {
+	fn kind() -> ItemKind { ItemKind::Template }
+	fn name(&self) -> String { self.class.name.name.clone() }
+
+	fn compare(&self, new: &ClassTemplate, d: &mut Differ) {
+		if self.params != new.params {
+			let names = |t: &ClassTemplate| t.params.iter().map(|p| p.name.clone()).collect::<Vec<_>>().join(", ");
+			d.changed(ItemKind::Template, format!("parameters <{}> -> <{}>", names(self), names(new)), true);
+		}
+		self.class.compare(&new.class, d);
+	}
+}
+
+
+
+impl Item for Field {
+	fn kind() -> ItemKind { ItemKind::Field }
+	fn name(&self) -> String { self.name.clone() }
+	fn is_exposed(&self) -> bool { self.access != Access::Private }
+
+	fn compare(&self, new: &Field, d: &mut Differ) {
+		let exposed = self.is_exposed();
+
+		if !self.ty.same_type(&new.ty) {
+			d.changed(ItemKind::Field, format!("type {} -> {}", self.ty, new.ty), exposed);
+		}
+		if self.is_static != new.is_static {
+			d.changed(ItemKind::Field, format!("static {} -> {}", self.is_static, new.is_static), exposed);
+		}
+		if self.offset != new.offset {
+			d.changed(ItemKind::Field, format!("bit offset {} -> {}", display_layout(self.offset), display_layout(new.offset)), exposed);
+		}
+		if self.bit_width != new.bit_width {
+			d.changed(ItemKind::Field, format!("bit width {} -> {}", display_layout(self.bit_width), display_layout(new.bit_width)), exposed);
+		}
+		if self.access != new.access {
+			d.changed(ItemKind::Field, format!("access {:?} -> {:?}", self.access, new.access), exposed && narrows(self.access, new.access));
+		}
+	}
+}
+
+
+
+impl Function {
+	fn is_exposed(&self) -> bool {
+		self.access != Access::Private
+	}
+}
+
+
+
+impl fmt::Display for Change {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		write!(f, "{} {} `{}`", match self.kind {
+			ChangeKind::Added => "Added",
+			ChangeKind::Removed => "Removed",
+			ChangeKind::Changed => "Changed",
+		}, self.item, self.path.join("::"))?;
+		if let Some(ref detail) = self.detail {
+			write!(f, ": {}", detail)?;
+		}
+
+		Ok(())
+	}
+}
+
+
+
+fn signature(f: &Function) -> String {
+	let mut sig = format!("{}({})", f.name, f.params.iter().map(|p| p.ty.to_string()).collect::<Vec<_>>().join(", "));
+	if f.is_const {
+		sig.push_str(" const");
+	}
+	if let Some(ref r) = f.return_ty {
+		sig.push_str(&format!(" -> {}", r));
+	}
+
+	sig
+}
+
+
+
+/// Whether going from `old` to `new` hides the item from some code that
+/// could see it before.
+fn narrows(old: Access, new: Access) -> bool {
+	let rank = |a: Access| match a {
+		Access::Public => 0,
+		Access::Protected => 1,
+		Access::Private => 2,
+	};
+	rank(new) > rank(old)
+}
+
+
+
+fn display_layout(n: Option<usize>) -> String {
+	n.map(|n| n.to_string()).unwrap_or_else(|| "unknown".to_string())
+}
+
+
+
+fn display_value(v: &Option<Value>) -> String {
+	v.as_ref().map(|v| v.to_string()).unwrap_or_else(|| "none".to_string())
+}
+
+
+
+#[cfg(test)]
+mod tests {
+	use super::{diff, ChangeKind};
+	use super::super::{Namespace, Class, Function, FunctionSemantic, TypeKind, Access, ItemKind};
+	use super::super::fixtures::{ty, scope, function, class};
+
+
+
+	fn method(name: &str, params: Vec<TypeKind>, access: Access) -> Function {
+		Function{semantic: FunctionSemantic::Method, access: access, ..function(name, params.into_iter().map(ty).collect())}
+	}
+
+
+
+	/// A root namespace holding class `C` with the given methods.
+	fn with_methods(methods: Vec<Function>) -> Namespace {
+		let mut root = Namespace::new(String::new());
+		root.classes.push(Class{methods: methods, ..class(scope("C"))});
+		root
+	}
+
+
+
+	#[test]
+	fn lone_overload_changed() {
+		let old = with_methods(vec![method("f", vec![TypeKind::Int], Access::Public)]);
+		let new = with_methods(vec![method("f", vec![TypeKind::Float], Access::Public)]);
+
+		let changes = diff(&old, &new);
+		assert_eq!(changes.len(), 1);
+		assert_eq!(changes[0].kind, ChangeKind::Changed);
+		assert_eq!(changes[0].item, ItemKind::Method);
+		assert_eq!(changes[0].path, vec!["C", "f"]);
+		assert_eq!(changes[0].detail, Some("f(int) -> f(float)".to_string()));
+		assert!(changes[0].breaking);
+	}
+
+
+
+	#[test]
+	fn renamed_overload_removed_and_added() {
+		let old = with_methods(vec![method("f", vec![TypeKind::Int], Access::Public)]);
+		let new = with_methods(vec![method("g", vec![TypeKind::Int], Access::Public)]);
+
+		let changes = diff(&old, &new);
+		assert_eq!(changes.iter().map(|c| (c.kind, c.path.join("::"), c.breaking)).collect::<Vec<_>>(), vec![
+			(ChangeKind::Removed, "C::f".to_string(), true),
+			(ChangeKind::Added, "C::g".to_string(), false),
+		]);
+	}
+
+
+
+	#[test]
+	fn ambiguous_overloads_not_paired() {
+		let old = with_methods(vec![method("f", vec![TypeKind::Int], Access::Public), method("f", vec![TypeKind::Bool], Access::Public)]);
+		let new = with_methods(vec![method("f", vec![TypeKind::Float], Access::Public), method("f", vec![TypeKind::Char], Access::Public)]);
+
+		let kinds = diff(&old, &new).iter().map(|c| c.kind).collect::<Vec<_>>();
+		assert_eq!(kinds, vec![ChangeKind::Removed, ChangeKind::Removed, ChangeKind::Added, ChangeKind::Added]);
+	}
+
+
+
+	#[test]
+	fn access_narrowing_breaks() {
+		let old = with_methods(vec![method("f", Vec::new(), Access::Public)]);
+		let new = with_methods(vec![method("f", Vec::new(), Access::Protected)]);
+
+		let changes = diff(&old, &new);
+		assert_eq!(changes.len(), 1);
+		assert_eq!(changes[0].detail, Some("access Public -> Protected".to_string()));
+		assert!(changes[0].breaking);
+
+		// Widening access is harmless, as is any change to a private member
+		let changes = diff(&new, &old);
+		assert_eq!(changes.len(), 1);
+		assert!(!changes[0].breaking);
+
+		let old = with_methods(vec![method("f", Vec::new(), Access::Private)]);
+		let changes = diff(&old, &new);
+		assert_eq!(changes.len(), 1);
+		assert!(!changes[0].breaking);
+	}
+}
@@ -0,0 +1,351 @@
+//! Consuming transformation of the API tree.
+//!
+//! Each `fold_*` method defaults to the matching `walk_*` function, which
+//! folds the children of the node and rebuilds it. Items that live in a list
+//! are folded to an `Option`, and returning `None` prunes them from the tree.
+
+use super::*;
+
+
+
+pub trait Fold {
+	fn fold_namespace(&mut self, n: Namespace) -> Option<Namespace> { Some(walk_namespace(self, n)) }
+	fn fold_global(&mut self, g: Var) -> Option<Var> { Some(walk_var(self, g)) }
+	fn fold_enum(&mut self, e: Enum) -> Option<Enum> { Some(walk_enum(self, e)) }
+	fn fold_variant(&mut self, v: Variant) -> Option<Variant> { Some(walk_variant(self, v)) }
+	fn fold_alias(&mut self, a: TypeAlias) -> Option<TypeAlias> { Some(walk_alias(self, a)) }
+	fn fold_class(&mut self, c: Class) -> Option<Class> { Some(walk_class(self, c)) }
+	fn fold_base(&mut self, b: Base) -> Option<Base> { Some(walk_base(self, b)) }
+	fn fold_template(&mut self, t: ClassTemplate) -> Option<ClassTemplate> { walk_template(self, t) }
+	fn fold_template_param(&mut self, p: TemplateParam) -> TemplateParam { walk_template_param(self, p) }
+	fn fold_field(&mut self, f: Field) -> Option<Field> { Some(walk_field(self, f)) }
+	fn fold_function(&mut self, f: Function) -> Option<Function> { Some(walk_function(self, f)) }
+	fn fold_param(&mut self, p: Var) -> Var { walk_var(self, p) }
+	fn fold_type(&mut self, t: TypeRef) -> TypeRef { walk_type(self, t) }
+	fn fold_scope_name(&mut self, s: ScopeName) -> ScopeName { walk_scope_name(self, s) }
+	fn fold_value(&mut self, v: Value) -> Value { walk_value(self, v) }
+	/// A qualified name kept as plain strings, such as the enum of a
+	/// `Value::EnumVariant`.
+	fn fold_path(&mut self, p: Vec<String>) -> Vec<String> { p }
+}
+
+
+
+pub fn walk_namespace<F: Fold + ?Sized>(folder: &mut F, n: Namespace) -> Namespace {
+	let Namespace{name, globals, enums, aliases, functions, classes, templates, namespaces} = n;
+
+	let globals = globals.into_iter().filter_map(|g| folder.fold_global(g)).collect();
+	let enums = enums.into_iter().filter_map(|e| folder.fold_enum(e)).collect();
+	let aliases = aliases.into_iter().filter_map(|a| folder.fold_alias(a)).collect();
+	let functions = functions.into_iter().filter_map(|f| folder.fold_function(f)).collect();
+	let classes = classes.into_iter().filter_map(|c| folder.fold_class(c)).collect();
+	let templates = templates.into_iter().filter_map(|t| folder.fold_template(t)).collect();
+	let namespaces = namespaces.into_iter().filter_map(|n| folder.fold_namespace(n)).collect();
+
+	Namespace{
+		name: name,
+		globals: globals,
+		enums: enums,
+		aliases: aliases,
+		functions: functions,
+		classes: classes,
+		templates: templates,
+		namespaces: namespaces,
+	}
+}
+
+
+
+pub fn walk_var<F: Fold + ?Sized>(folder: &mut F, var: Var) -> Var {
+	let Var{name, ty, location, doc} = var;
+
+	Var{
+		name: name,
+		ty: folder.fold_type(ty),
+		location: location,
+		doc: doc,
+	}
+}
+
+
+
+pub fn walk_enum<F: Fold + ?Sized>(folder: &mut F, e: Enum) -> Enum {
+	let Enum{name, underlying, variants, location, doc} = e;
+
+	Enum{
+		name: name,
+		underlying: underlying,
+		variants: variants.into_iter().filter_map(|v| folder.fold_variant(v)).collect(),
+		location: location,
+		doc: doc,
+	}
+}
+
+
+
+pub fn walk_variant<F: Fold + ?Sized>(folder: &mut F, variant: Variant) -> Variant {
+	let Variant{name, value, location, doc} = variant;
+
+	Variant{
+		name: name,
+		value: folder.fold_value(value),
+		location: location,
+		doc: doc,
+	}
+}
+
+
+
+pub fn walk_alias<F: Fold + ?Sized>(folder: &mut F, a: TypeAlias) -> TypeAlias {
+	let TypeAlias{name, ty, location} = a;
+
+	let name = folder.fold_scope_name(name);
+	TypeAlias{
+		name: name,
+		ty: folder.fold_type(ty),
+		location: location,
+	}
+}
+
+
+
+pub fn walk_class<F: Fold + ?Sized>(folder: &mut F, c: Class) -> Class {
+	let Class{
+		include,
+		location,
+		doc,
+		name,
+		bases,
+		is_pod,
+		is_union,
+		size,
+		align,
+		enums,
+		aliases,
+		fields,
+		anon_unions,
+		ctors,
+		methods,
+		virtual_dtor,
+		classes,
+	} = c;
+
+	let name = folder.fold_scope_name(name);
+	let bases = bases.into_iter().filter_map(|b| folder.fold_base(b)).collect();
+	let enums = enums.into_iter().filter_map(|e| folder.fold_enum(e)).collect();
+	let aliases = aliases.into_iter().filter_map(|a| folder.fold_alias(a)).collect();
+	let fields = fields.into_iter().filter_map(|f| folder.fold_field(f)).collect();
+	let anon_unions = anon_unions.into_iter().filter_map(|u| folder.fold_class(u)).collect();
+	let ctors = ctors.into_iter().filter_map(|f| folder.fold_function(f)).collect();
+	let methods = methods.into_iter().filter_map(|f| folder.fold_function(f)).collect();
+	let classes = classes.into_iter().filter_map(|c| folder.fold_class(c)).collect();
+
+	Class{
+		include: include,
+		location: location,
+		doc: doc,
+		name: name,
+		bases: bases,
+		is_pod: is_pod,
+		is_union: is_union,
+		size: size,
+		align: align,
+		enums: enums,
+		aliases: aliases,
+		fields: fields,
+		anon_unions: anon_unions,
+		ctors: ctors,
+		methods: methods,
+		virtual_dtor: virtual_dtor,
+		classes: classes,
+	}
+}
+
+
+
+pub fn walk_base<F: Fold + ?Sized>(folder: &mut F, b: Base) -> Base {
+	let Base{ty, access, is_virtual, offset} = b;
+
+	Base{
+		ty: folder.fold_type(ty),
+		access: access,
+		is_virtual: is_virtual,
+		offset: offset,
+	}
+}
+
+
+
+/// Pruning the pattern class prunes the whole template.
+pub fn walk_template<F: Fold + ?Sized>(folder: &mut F, t: ClassTemplate) -> Option<ClassTemplate> {
+	let ClassTemplate{params, class} = t;
+
+	let params = params.into_iter().map(|p| folder.fold_template_param(p)).collect();
+	folder.fold_class(class).map(|class| ClassTemplate{
+		params: params,
+		class: class,
+	})
+}
+
+
+
+pub fn walk_template_param<F: Fold + ?Sized>(folder: &mut F, p: TemplateParam) -> TemplateParam {
+	let TemplateParam{name, kind} = p;
+
+	TemplateParam{
+		name: name,
+		kind: match kind {
+			TemplateParamKind::Value(ty) => TemplateParamKind::Value(folder.fold_type(ty)),
+			k @ TemplateParamKind::Type | k @ TemplateParamKind::Template => k,
+		},
+	}
+}
+
+
+
+pub fn walk_field<F: Fold + ?Sized>(folder: &mut F, f: Field) -> Field {
+	let Field{name, ty, access, is_static, offset, bit_width, location, doc} = f;
+
+	Field{
+		name: name,
+		ty: folder.fold_type(ty),
+		access: access,
+		is_static: is_static,
+		offset: offset,
+		bit_width: bit_width,
+		location: location,
+		doc: doc,
+	}
+}
+
+
+
+pub fn walk_function<F: Fold + ?Sized>(folder: &mut F, f: Function) -> Function {
+	let Function{name, params, return_ty, semantic, access, is_const, location, doc} = f;
+
+	let params = params.into_iter().map(|p| folder.fold_param(p)).collect();
+	Function{
+		name: name,
+		params: params,
+		return_ty: return_ty.map(|r| folder.fold_type(r)),
+		semantic: semantic,
+		access: access,
+		is_const: is_const,
+		location: location,
+		doc: doc,
+	}
+}
+
+
+
+pub fn walk_type<F: Fold + ?Sized>(folder: &mut F, t: TypeRef) -> TypeRef {
+	let TypeRef{kind, is_const, value} = t;
+
+	let kind = match kind {
+		TypeKind::Elaborated(path) => TypeKind::Elaborated(path.into_iter().map(|s| folder.fold_scope_name(s)).collect()),
+		TypeKind::Pointer(t) => TypeKind::Pointer(Box::new(folder.fold_type(*t))),
+		TypeKind::Reference(t) => TypeKind::Reference(Box::new(folder.fold_type(*t))),
+		TypeKind::RValueReference(t) => TypeKind::RValueReference(Box::new(folder.fold_type(*t))),
+		TypeKind::Array(size, t) => TypeKind::Array(size, Box::new(folder.fold_type(*t))),
+		TypeKind::FunctionPointer{params, return_ty, variadic} => {
+			let params = params.into_iter().map(|p| folder.fold_type(p)).collect();
+			TypeKind::FunctionPointer{
+				params: params,
+				return_ty: return_ty.map(|r| Box::new(folder.fold_type(*r))),
+				variadic: variadic,
+			}
+		},
+		k @ TypeKind::Void | k @ TypeKind::Bool | k @ TypeKind::Char | k @ TypeKind::UChar | k @ TypeKind::WChar
+		| k @ TypeKind::Short | k @ TypeKind::UShort | k @ TypeKind::Int | k @ TypeKind::UInt | k @ TypeKind::Long | k @ TypeKind::ULong
+		| k @ TypeKind::LongLong | k @ TypeKind::ULongLong | k @ TypeKind::Float | k @ TypeKind::Double
+		| k @ TypeKind::TemplateParam(_) | k @ TypeKind::Opaque{..}
+		=> k,
+	};
+
+	TypeRef{
+		kind: kind,
+		is_const: is_const,
+		value: value.map(|v| folder.fold_value(v)),
+	}
+}
+
+
+
+pub fn walk_value<F: Fold + ?Sized>(folder: &mut F, v: Value) -> Value {
+	match v {
+		Value::Construct{ty, args} => {
+			let ty = folder.fold_type(*ty);
+			Value::Construct{
+				ty: Box::new(ty),
+				args: args.into_iter().map(|a| folder.fold_value(a)).collect(),
+			}
+		},
+		Value::EnumVariant{path, name} => Value::EnumVariant{
+			path: folder.fold_path(path),
+			name: name,
+		},
+		v @ Value::Int(_) | v @ Value::UInt(_) | v @ Value::Float(_) | v @ Value::Double(_) | v @ Value::String(_)
+		| v @ Value::Bool(_) | v @ Value::Null | v @ Value::Raw(_)
+		=> v,
+	}
+}
+
+
+
+pub fn walk_scope_name<F: Fold + ?Sized>(folder: &mut F, s: ScopeName) -> ScopeName {
+	let ScopeName{name, args} = s;
+
+	ScopeName{
+		name: name,
+		args: args.into_iter().map(|a| folder.fold_type(a)).collect(),
+	}
+}
+
+
+
+#[cfg(test)]
+mod tests {
+	use super::Fold;
+	use super::super::Value;
+	use super::super::fixtures::everything;
+
+
+
+	struct Identity;
+
+	impl Fold for Identity {}
+
+
+
+	/// Qualifies every path with `ns`.
+	struct Qualify;
+
+	impl Fold for Qualify {
+		fn fold_path(&mut self, mut p: Vec<String>) -> Vec<String> {
+			p.insert(0, "ns".to_string());
+			p
+		}
+	}
+
+
+
+	#[test]
+	fn identity_round_trip() {
+		let root = everything();
+		let folded = Identity.fold_namespace(root.clone()).unwrap();
+
+		assert_eq!(format!("{:?}", folded), format!("{:?}", root));
+	}
+
+
+
+	#[test]
+	fn paths_folded() {
+		let root = Qualify.fold_namespace(everything()).unwrap();
+
+		assert_eq!(root.classes[0].methods[0].params[0].ty.value, Some(Value::EnumVariant{
+			path: vec!["ns".to_string(), "E".to_string()],
+			name: "A".to_string(),
+		}));
+	}
+}
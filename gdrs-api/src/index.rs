@@ -0,0 +1,231 @@
+use std::collections::HashMap;
+
+use super::{Namespace, Class, ClassTemplate, Enum, TypeAlias, TypeRef, TypeKind, ScopeName};
+
+
+
+/// Anything a qualified path can name.
+#[derive(Copy, Clone, Debug)]
+pub enum Item<'a> {
+	Namespace(&'a Namespace),
+	Class(&'a Class),
+	Template(&'a ClassTemplate),
+	Enum(&'a Enum),
+	Alias(&'a TypeAlias),
+}
+
+
+
+/// Resolves qualified paths, such as the ones in `TypeKind::Elaborated`, to
+/// the items they name. Paths are outermost first and don't include the
+/// root namespace.
+pub struct Index<'a> {
+	// Keyed by bare names; several entries can share a key when they only
+	// differ in template arguments.
+	items: HashMap<Vec<String>, Vec<(Vec<ScopeName>, Item<'a>)>>,
+}
+
+
+
+impl<'a> Index<'a> {
+	pub fn new(root: &'a Namespace) -> Index<'a> {
+		let mut index = Index{items: HashMap::new()};
+		index.add_namespace(root, &mut Vec::new());
+		index
+	}
+
+
+
+	pub fn resolve(&self, path: &[ScopeName]) -> Option<Item<'a>> {
+		let candidates = match self.items.get(&bare_path(path)) {
+			Some(candidates) => candidates,
+			None => return None,
+		};
+
+		if let Some(&(_, item)) = candidates.iter().find(|&&(ref p, _)| &p[..] == path) {
+			return Some(item);
+		}
+
+		// A specialization that was never instantiated still resolves to its
+		// template.
+		if path.last().map(|s| !s.args.is_empty()).unwrap_or(false) {
+			return candidates.iter().filter_map(|&(_, item)| match item {
+				Item::Template(_) => Some(item),
+				_ => None,
+			}).next();
+		}
+
+		None
+	}
+
+
+
+	/// Looks up a `::` separated path, ignoring template arguments. All items
+	/// matching the bare names are returned, e.g. every instantiation of a
+	/// template along with the template itself.
+	pub fn lookup(&self, path: &str) -> Vec<Item<'a>> {
+		let key = path.trim_left_matches("::").split("::").map(|s| s.to_string()).collect::<Vec<_>>();
+		self.items.get(&key).map(|c| c.iter().map(|&(_, item)| item).collect()).unwrap_or_else(Vec::new)
+	}
+
+
+
+	/// Resolves the item a type names, if it is a named type.
+	pub fn resolve_type(&self, ty: &TypeRef) -> Option<Item<'a>> {
+		match ty.kind {
+			TypeKind::Elaborated(ref path) => self.resolve(path),
+			_ => None,
+		}
+	}
+
+
+
+	/// Follows a chain of aliases to the type it finally stands for. Stops at
+	/// the last alias that can be resolved.
+	pub fn resolve_alias(&self, alias: &'a TypeAlias) -> &'a TypeRef {
+		let mut ty = &alias.ty;
+		let mut seen = vec![alias as *const TypeAlias];
+		while let Some(Item::Alias(next)) = self.resolve_type(ty) {
+			if seen.contains(&(next as *const TypeAlias)) {
+				break;
+			}
+			seen.push(next);
+			ty = &next.ty;
+		}
+
+		ty
+	}
+
+
+
+	fn add_namespace(&mut self, ns: &'a Namespace, path: &mut Vec<ScopeName>) {
+		for e in ns.enums.iter() {
+			self.add(path, scope(&e.name), Item::Enum(e));
+		}
+		for a in ns.aliases.iter() {
+			self.add(path, a.name.clone(), Item::Alias(a));
+		}
+		for c in ns.classes.iter() {
+			self.add_class(c, path);
+		}
+		for t in ns.templates.iter() {
+			self.add(path, t.class.name.clone(), Item::Template(t));
+		}
+		for n in ns.namespaces.iter() {
+			self.add(path, scope(&n.name), Item::Namespace(n));
+			path.push(scope(&n.name));
+			self.add_namespace(n, path);
+			path.pop();
+		}
+	}
+
+
+
+	fn add_class(&mut self, c: &'a Class, path: &mut Vec<ScopeName>) {
+		self.add(path, c.name.clone(), Item::Class(c));
+
+		path.push(c.name.clone());
+		for e in c.enums.iter() {
+			self.add(path, scope(&e.name), Item::Enum(e));
+		}
+		for a in c.aliases.iter() {
+			self.add(path, a.name.clone(), Item::Alias(a));
+		}
+		for nested in c.classes.iter() {
+			self.add_class(nested, path);
+		}
+		path.pop();
+	}
+
+
+
+	fn add(&mut self, path: &[ScopeName], name: ScopeName, item: Item<'a>) {
+		let mut full = path.to_vec();
+		full.push(name);
+		self.items.entry(bare_path(&full)).or_insert_with(Vec::new).push((full, item));
+	}
+}
+
+
+
+fn scope(name: &str) -> ScopeName {
+	ScopeName{name: name.to_string(), args: Vec::with_capacity(0)}
+}
+
+
+
+fn bare_path(path: &[ScopeName]) -> Vec<String> {
+	path.iter().map(|s| s.name.clone()).collect()
+}
+
+
+
+#[cfg(test)]
+mod tests {
+	use super::{Index, Item};
+	use super::super::{Namespace, Class, ClassTemplate, TemplateParam, TemplateParamKind, TypeKind, ScopeName};
+	use super::super::fixtures::{ty, scope, class};
+
+
+
+	fn instance(name: &str, arg: TypeKind) -> ScopeName {
+		ScopeName{name: name.to_string(), args: vec![ty(arg)]}
+	}
+
+
+
+	/// `ns::Outer::Inner`, plus template `ns::Vec<T>` with `Vec<int>`
+	/// instantiated.
+	fn root() -> Namespace {
+		let mut ns = Namespace::new("ns".to_string());
+		ns.classes.push(Class{classes: vec![class(scope("Inner"))], ..class(scope("Outer"))});
+		ns.classes.push(class(instance("Vec", TypeKind::Int)));
+		ns.templates.push(ClassTemplate{
+			params: vec![TemplateParam{name: "T".to_string(), kind: TemplateParamKind::Type}],
+			class: class(scope("Vec")),
+		});
+
+		let mut root = Namespace::new(String::new());
+		root.namespaces.push(ns);
+		root
+	}
+
+
+
+	fn name(item: Option<Item>) -> Option<String> {
+		item.map(|item| match item {
+			Item::Namespace(n) => format!("namespace {}", n.name),
+			Item::Class(c) => format!("class {}", c.name.name),
+			Item::Template(t) => format!("template {}", t.class.name.name),
+			Item::Enum(e) => format!("enum {}", e.name),
+			Item::Alias(a) => format!("alias {}", a.name.name),
+		})
+	}
+
+
+
+	#[test]
+	fn resolve_outermost_first() {
+		let root = root();
+		let index = Index::new(&root);
+
+		assert_eq!(name(index.resolve(&[scope("ns")])), Some("namespace ns".to_string()));
+		assert_eq!(name(index.resolve(&[scope("ns"), scope("Outer"), scope("Inner")])), Some("class Inner".to_string()));
+		assert_eq!(name(index.resolve(&[scope("Inner"), scope("Outer"), scope("ns")])), None);
+		assert_eq!(name(index.resolve(&[scope("Outer")])), None);
+	}
+
+
+
+	#[test]
+	fn resolve_template_arguments() {
+		let root = root();
+		let index = Index::new(&root);
+
+		assert_eq!(name(index.resolve(&[scope("ns"), instance("Vec", TypeKind::Int)])), Some("class Vec".to_string()));
+		// Without an instantiation, the template itself
+		assert_eq!(name(index.resolve(&[scope("ns"), instance("Vec", TypeKind::Float)])), Some("template Vec".to_string()));
+		assert_eq!(name(index.resolve(&[scope("ns"), scope("Vec")])), Some("template Vec".to_string()));
+		assert_eq!(index.lookup("::ns::Vec").len(), 2);
+	}
+}
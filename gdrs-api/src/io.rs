@@ -0,0 +1,290 @@
+use std::error;
+use std::fmt;
+use std::io::{self, Read, Write};
+use std::path::Path;
+use serde_json;
+use bincode::{self, SizeLimit};
+use bincode::serde::{SerializeError, DeserializeError};
+
+use super::{ApiDocument, SCHEMA_VERSION};
+
+
+
+#[derive(Debug)]
+pub enum Error {
+	Io(io::Error),
+	Json(serde_json::Error),
+	Encode(SerializeError),
+	Decode(DeserializeError),
+	/// A binary document didn't start with `MAGIC`.
+	NotBinary,
+	/// The document predates `ApiDocument` and has no schema version at all.
+	Unversioned,
+	SchemaVersion{found: u32, expected: u32},
+}
+
+
+
+/// Leads a binary document, followed by the schema version and then the
+/// bincode encoded `ApiDocument`.
+pub const MAGIC: &'static [u8; 4] = b"GDRS";
+
+
+
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub enum Format {
+	Json,
+	/// Compact bincode encoding, much faster to load than JSON.
+	Binary,
+}
+
+
+
+impl Format {
+	/// Picks the format from the file extension: `.bin` is binary, anything
+	/// else is JSON.
+	pub fn from_path<P: AsRef<Path>>(path: P) -> Format {
+		match path.as_ref().extension().and_then(|e| e.to_str()) {
+			Some("bin") => Format::Binary,
+			_ => Format::Json,
+		}
+	}
+}
+
+
+
+/// Just enough of an `ApiDocument` to check its version before committing
+/// to deserializing the rest of it.
+#[derive(Deserialize)]
+struct Header {
+	schema_version: u32,
+}
+
+
+
+pub fn read<R: Read>(reader: R, format: Format) -> Result<ApiDocument, Error> {
+	match format {
+		Format::Json => read_json(reader),
+		Format::Binary => read_binary(reader),
+	}
+}
+
+
+
+pub fn write<W: Write>(writer: &mut W, doc: &ApiDocument, format: Format) -> Result<(), Error> {
+	match format {
+		Format::Json => write_json(writer, doc),
+		Format::Binary => write_binary(writer, doc),
+	}
+}
+
+
+
+pub fn read_json<R: Read>(mut reader: R) -> Result<ApiDocument, Error> {
+	let mut source = String::new();
+	reader.read_to_string(&mut source)?;
+
+	let header: Header = serde_json::from_str(&source).map_err(|e| match e {
+		serde_json::Error::Syntax(serde_json::ErrorCode::MissingField("schema_version"), _, _) => Error::Unversioned,
+		e => Error::Json(e),
+	})?;
+	check_version(header.schema_version)?;
+
+	Ok(serde_json::from_str(&source)?)
+}
+
+
+
+pub fn write_json<W: Write>(writer: &mut W, doc: &ApiDocument) -> Result<(), Error> {
+	serde_json::to_writer_pretty(writer, doc)?;
+	Ok(())
+}
+
+
+
+pub fn read_binary<R: Read>(mut reader: R) -> Result<ApiDocument, Error> {
+	let mut magic = [0u8; 4];
+	reader.read_exact(&mut magic)?;
+	if &magic != MAGIC {
+		return Err(Error::NotBinary);
+	}
+
+	let version: u32 = bincode::serde::deserialize_from(&mut reader, SizeLimit::Infinite)?;
+	check_version(version)?;
+
+	Ok(bincode::serde::deserialize_from(&mut reader, SizeLimit::Infinite)?)
+}
+
+
+
+pub fn write_binary<W: Write>(writer: &mut W, doc: &ApiDocument) -> Result<(), Error> {
+	writer.write_all(MAGIC)?;
+	bincode::serde::serialize_into(writer, &SCHEMA_VERSION, SizeLimit::Infinite)?;
+	bincode::serde::serialize_into(writer, doc, SizeLimit::Infinite)?;
+	Ok(())
+}
+
+
+
+pub fn check_version(version: u32) -> Result<(), Error> {
+	if version == SCHEMA_VERSION {
+		Ok(())
+	} else {
+		Err(Error::SchemaVersion{found: version, expected: SCHEMA_VERSION})
+	}
+}
+
+
+
+impl fmt::Display for Error {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		match *self {
+			Error::Io(ref e) => write!(f, "{}", e),
+			Error::Json(ref e) => write!(f, "{}", e),
+			Error::Encode(ref e) => write!(f, "{}", e),
+			Error::Decode(ref e) => write!(f, "{}", e),
+			Error::NotBinary => write!(f, "not a binary API description"),
+			Error::Unversioned => write!(f, "API description has no schema version; regenerate it with a current gdrs-parse"),
+			Error::SchemaVersion{found, expected} => write!(
+				f,
+				"API description has schema version {}, but version {} is required; regenerate it with a matching gdrs-parse",
+				found,
+				expected
+			),
+		}
+	}
+}
+
+
+
+impl error::Error for Error {
+	fn description(&self) -> &str {
+		match *self {
+			Error::Io(ref e) => e.description(),
+			Error::Json(ref e) => e.description(),
+			Error::Encode(ref e) => e.description(),
+			Error::Decode(ref e) => e.description(),
+			Error::NotBinary => "not a binary API description",
+			Error::Unversioned => "unversioned API description",
+			Error::SchemaVersion{..} => "API description schema version mismatch",
+		}
+	}
+}
+
+
+
+impl From<io::Error> for Error {
+	fn from(e: io::Error) -> Error {
+		Error::Io(e)
+	}
+}
+
+
+
+impl From<serde_json::Error> for Error {
+	fn from(e: serde_json::Error) -> Error {
+		Error::Json(e)
+	}
+}
+
+
+
+impl From<SerializeError> for Error {
+	fn from(e: SerializeError) -> Error {
+		Error::Encode(e)
+	}
+}
+
+
+
+impl From<DeserializeError> for Error {
+	fn from(e: DeserializeError) -> Error {
+		Error::Decode(e)
+	}
+}
+
+
+
+#[cfg(test)]
+mod tests {
+	use bincode::{self, SizeLimit};
+	use super::{read_json, write_json, read_binary, write_binary, Error, MAGIC};
+	use super::super::SCHEMA_VERSION;
+	use super::super::fixtures::{document, everything};
+
+
+
+	#[test]
+	fn json_round_trip() {
+		let doc = document(everything());
+		let mut bytes = Vec::new();
+		write_json(&mut bytes, &doc).unwrap();
+
+		assert_eq!(format!("{:?}", read_json(&bytes[..]).unwrap()), format!("{:?}", doc));
+	}
+
+
+
+	#[test]
+	fn unversioned_json() {
+		match read_json(&br#"{"name": "", "globals": [], "classes": []}"#[..]) {
+			Err(Error::Unversioned) => (),
+			r => panic!("expected Error::Unversioned, got {:?}", r),
+		}
+	}
+
+
+
+	#[test]
+	fn newer_json() {
+		let source = format!(r#"{{"schema_version": {}, "root": {{}}}}"#, SCHEMA_VERSION + 1);
+		match read_json(source.as_bytes()) {
+			Err(Error::SchemaVersion{found, expected}) => {
+				assert_eq!(found, SCHEMA_VERSION + 1);
+				assert_eq!(expected, SCHEMA_VERSION);
+			},
+			r => panic!("expected Error::SchemaVersion, got {:?}", r),
+		}
+	}
+
+
+
+	#[test]
+	fn binary_round_trip() {
+		let doc = document(everything());
+		let mut bytes = Vec::new();
+		write_binary(&mut bytes, &doc).unwrap();
+
+		assert_eq!(&bytes[..4], &MAGIC[..]);
+		assert_eq!(format!("{:?}", read_binary(&bytes[..]).unwrap()), format!("{:?}", doc));
+	}
+
+
+
+	#[test]
+	fn json_is_not_binary() {
+		let mut bytes = Vec::new();
+		write_json(&mut bytes, &document(everything())).unwrap();
+
+		match read_binary(&bytes[..]) {
+			Err(Error::NotBinary) => (),
+			r => panic!("expected Error::NotBinary, got {:?}", r),
+		}
+	}
+
+
+
+	#[test]
+	fn newer_binary() {
+		let mut bytes = MAGIC.to_vec();
+		bincode::serde::serialize_into(&mut bytes, &(SCHEMA_VERSION + 1), SizeLimit::Infinite).unwrap();
+
+		match read_binary(&bytes[..]) {
+			Err(Error::SchemaVersion{found, expected}) => {
+				assert_eq!(found, SCHEMA_VERSION + 1);
+				assert_eq!(expected, SCHEMA_VERSION);
+			},
+			r => panic!("expected Error::SchemaVersion, got {:?}", r),
+		}
+	}
+}
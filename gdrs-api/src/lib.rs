@@ -3,6 +3,42 @@
 extern crate serde;
 #[macro_use]
 extern crate serde_derive;
+extern crate serde_json;
+extern crate bincode;
+
+use std::fmt;
+
+pub mod io;
+pub mod index;
+pub mod visit;
+pub mod visit_mut;
+pub mod fold;
+pub mod diff;
+
+
+
+/// Version of the document layout below. Bump this whenever a change to the
+/// model would make older readers misinterpret a document.
+///
+/// 2: `TypeKind::Elaborated` paths are outermost first.
+/// 3: `Value` gains `Bool`, `Null`, `EnumVariant`, `Construct` and `Raw`.
+/// 4: `TypeKind::Opaque` for types that have no other representation.
+pub const SCHEMA_VERSION: u32 = 4;
+
+
+
+/// Top level of a generated API description.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct ApiDocument {
+	pub schema_version: u32,
+	pub godot_version: Option<String>,
+	pub target: Option<String>,
+	pub defines: Vec<String>,
+	pub generator_version: String,
+	/// Seconds since the UNIX epoch.
+	pub generated_at: u64,
+	pub root: Namespace,
+}
 
 
 
@@ -14,6 +50,7 @@ pub struct Namespace {
 	pub aliases: Vec<TypeAlias>,
 	pub functions: Vec<Function>,
 	pub classes: Vec<Class>,
+	pub templates: Vec<ClassTemplate>,
 	pub namespaces: Vec<Namespace>,
 }
 
@@ -23,6 +60,8 @@ pub struct Namespace {
 pub struct Var {
 	pub name: String,
 	pub ty: TypeRef,
+	pub location: Option<SourceLocation>,
+	pub doc: Option<Doc>,
 }
 
 
@@ -32,6 +71,8 @@ pub struct Enum {
 	pub name: String,
 	pub underlying: TypeKind,
 	pub variants: Vec<Variant>,
+	pub location: Option<SourceLocation>,
+	pub doc: Option<Doc>,
 }
 
 
@@ -40,6 +81,8 @@ pub struct Enum {
 pub struct Variant {
 	pub name: String,
 	pub value: Value,
+	pub location: Option<SourceLocation>,
+	pub doc: Option<Doc>,
 }
 
 
@@ -48,6 +91,7 @@ pub struct Variant {
 pub struct TypeAlias {
 	pub name: ScopeName,
 	pub ty: TypeRef,
+	pub location: Option<SourceLocation>,
 }
 
 
@@ -55,10 +99,15 @@ pub struct TypeAlias {
 #[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct Class {
 	pub include: String,
+	pub location: Option<SourceLocation>,
+	pub doc: Option<Doc>,
 	pub name: ScopeName,
-	pub inherits: Option<TypeRef>,
+	pub bases: Vec<Base>,
 	pub is_pod: bool,
 	pub is_union: bool,
+	/// Size and alignment in bytes, if the class is complete.
+	pub size: Option<usize>,
+	pub align: Option<usize>,
 	pub enums: Vec<Enum>,
 	pub aliases: Vec<TypeAlias>,
 	pub fields: Vec<Field>,
@@ -71,10 +120,69 @@ pub struct Class {
 
 
 
+#[derive(Clone, PartialEq, Debug, Serialize, Deserialize)]
+pub struct Base {
+	pub ty: TypeRef,
+	pub access: Access,
+	pub is_virtual: bool,
+	/// Offset in bits from the start of the class, if known. libclang can't
+	/// report base offsets, so only a base that is certain to come first is
+	/// given one.
+	pub offset: Option<usize>,
+}
+
+
+
+/// A class template. Members are expressed in terms of the template
+/// parameters through `TypeKind::TemplateParam`; concrete instantiations are
+/// emitted as ordinary classes whose `name.args` hold the arguments.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct ClassTemplate {
+	pub params: Vec<TemplateParam>,
+	pub class: Class,
+}
+
+
+
+#[derive(Clone, PartialEq, Debug, Serialize, Deserialize)]
+pub struct TemplateParam {
+	pub name: String,
+	pub kind: TemplateParamKind,
+}
+
+
+
+#[derive(Clone, PartialEq, Debug, Serialize, Deserialize)]
+pub enum TemplateParamKind {
+	Type,
+	Value(TypeRef),
+	Template,
+}
+
+
+
+#[derive(Clone, PartialEq, Eq, Hash, Debug, Serialize, Deserialize)]
+pub struct SourceLocation {
+	pub file: String,
+	pub line: u32,
+	pub column: u32,
+}
+
+
+
+#[derive(Clone, PartialEq, Eq, Debug, Serialize, Deserialize)]
+pub struct Doc {
+	pub raw: String,
+	pub brief: Option<String>,
+}
+
+
+
 #[derive(Copy, Clone, PartialEq, Eq, Debug, Serialize, Deserialize)]
 pub enum Access {
 	Public,
 	Protected,
+	Private,
 }
 
 
@@ -89,26 +197,19 @@ pub enum FunctionSemantic {
 
 
 
-#[derive(Clone, PartialEq, Eq, Debug, Serialize, Deserialize)]
-pub enum TypeSemantic {
-	Value,
-	Pointer,
-	PointerToPointer,
-	Reference,
-	ReferenceToPointer,
-	Array(usize),
-	ArrayOfArray(usize, usize),
-	ArrayOfPointer(usize),
-}
-
-
-
 #[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct Field {
 	pub name: String,
 	pub ty: TypeRef,
 	pub access: Access,
 	pub is_static: bool,
+	/// Offset in bits from the start of the class, for non-static fields.
+	/// Private fields are listed without a name, as padding.
+	pub offset: Option<usize>,
+	/// Width in bits, for bit-fields.
+	pub bit_width: Option<usize>,
+	pub location: Option<SourceLocation>,
+	pub doc: Option<Doc>,
 }
 
 
@@ -121,6 +222,8 @@ pub struct Function {
 	pub semantic: FunctionSemantic,
 	pub access: Access,
 	pub is_const: bool,
+	pub location: Option<SourceLocation>,
+	pub doc: Option<Doc>,
 }
 
 
@@ -128,7 +231,6 @@ pub struct Function {
 #[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
 pub struct TypeRef {
 	pub kind: TypeKind,
-	pub semantic: TypeSemantic,
 	pub is_const: bool,
 	pub value: Option<Value>,
 }
@@ -152,7 +254,26 @@ pub enum TypeKind {
 	ULongLong,
 	Float,
 	Double,
+	/// A named type, as a fully qualified path from the outermost scope.
 	Elaborated(Vec<ScopeName>),
+	Pointer(Box<TypeRef>),
+	Reference(Box<TypeRef>),
+	RValueReference(Box<TypeRef>),
+	/// Element count is `None` for arrays of unknown bound (`T[]`).
+	Array(Option<usize>, Box<TypeRef>),
+	FunctionPointer {
+		params: Vec<TypeRef>,
+		return_ty: Option<Box<TypeRef>>,
+		variadic: bool,
+	},
+	TemplateParam(String),
+	/// A type that can't be represented otherwise, such as a member pointer,
+	/// kept with its C++ spelling and layout.
+	Opaque {
+		spelling: String,
+		size: Option<usize>,
+		align: Option<usize>,
+	},
 }
 
 
@@ -172,42 +293,146 @@ pub enum Value {
 	Float(f32),
 	Double(f64),
 	String(String),
+	Bool(bool),
+	/// A null pointer, whether spelled `NULL`, `0` or `nullptr`.
+	Null,
+	/// A reference to an enum constant. `path` is the qualified name of the
+	/// enum, or of the enclosing scope for anonymous enums, whose constants
+	/// are recorded there directly.
+	EnumVariant {
+		path: Vec<String>,
+		name: String,
+	},
+	/// A constructor call or functional cast, such as `Vector2()` or
+	/// `Color(1, 1, 1)`.
+	Construct {
+		ty: Box<TypeRef>,
+		args: Vec<Value>,
+	},
+	/// Source text of an expression that couldn't be evaluated.
+	Raw(String),
+}
+
+
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Conflict {
+	pub path: Vec<String>,
+	pub kind: ItemKind,
+	pub existing: Option<SourceLocation>,
+	pub incoming: Option<SourceLocation>,
+}
+
+
+
+#[derive(Copy, Clone, PartialEq, Eq, Debug, Serialize, Deserialize)]
+pub enum ItemKind {
+	Namespace,
+	Global,
+	Enum,
+	Variant,
+	Alias,
+	Function,
+	Class,
+	Base,
+	Template,
+	Field,
+	Constructor,
+	Method,
 }
 
 
 
 impl Namespace {
-	pub fn merge(&mut self, src: Namespace) {
-		let Namespace{name: _, globals, enums, aliases, classes, functions, namespaces} = src;
+	pub fn new(name: String) -> Namespace {
+		Namespace{
+			name: name,
+			globals: Vec::with_capacity(0),
+			enums: Vec::with_capacity(0),
+			aliases: Vec::with_capacity(0),
+			functions: Vec::with_capacity(0),
+			classes: Vec::with_capacity(0),
+			templates: Vec::with_capacity(0),
+			namespaces: Vec::with_capacity(0),
+		}
+	}
+
+
+
+	/// Merges `src` into `self`. Items that are already present with the same
+	/// definition are dropped, function overloads are kept, and items that
+	/// share a name but differ in definition are returned as conflicts (the
+	/// existing item wins).
+	pub fn merge(&mut self, src: Namespace) -> Vec<Conflict> {
+		let mut conflicts = Vec::new();
+		self.merge_at(src, &mut Vec::new(), &mut conflicts);
+		conflicts
+	}
+
+
+
+	fn merge_at(&mut self, src: Namespace, path: &mut Vec<String>, conflicts: &mut Vec<Conflict>) {
+		let Namespace{name: _, globals, enums, aliases, classes, templates, functions, namespaces} = src;
 
 		for sg in globals.into_iter() {
-			if !self.globals.iter().any(|dg| dg.name == sg.name) {
-				self.globals.push(sg);
+			if let Some(dg) = self.globals.iter().find(|dg| dg.name == sg.name) {
+				if !dg.same_definition(&sg) {
+					conflicts.push(Conflict::new(path, &sg.name, ItemKind::Global, &dg.location, sg.location));
+				}
+				continue;
 			}
+			self.globals.push(sg);
 		}
 		for se in enums.into_iter() {
-			if !self.enums.iter().any(|de| de.name == se.name) {
-				self.enums.push(se);
+			if let Some(de) = self.enums.iter().find(|de| de.name == se.name) {
+				if !de.same_definition(&se) {
+					conflicts.push(Conflict::new(path, &se.name, ItemKind::Enum, &de.location, se.location));
+				}
+				continue;
 			}
+			self.enums.push(se);
 		}
 		for sa in aliases.into_iter() {
-			if !self.aliases.iter().any(|da| da.name == sa.name) {
-				self.aliases.push(sa);
+			if let Some(da) = self.aliases.iter().find(|da| da.name == sa.name) {
+				if !da.same_definition(&sa) {
+					conflicts.push(Conflict::new(path, &sa.name.name, ItemKind::Alias, &da.location, sa.location));
+				}
+				continue;
 			}
+			self.aliases.push(sa);
 		}
 		for sf in functions.into_iter() {
-			if !self.functions.iter().any(|df| df.name == sf.name) {
-				self.functions.push(sf);
+			if let Some(df) = self.functions.iter().find(|df| df.same_signature(&sf)) {
+				if !df.same_definition(&sf) {
+					conflicts.push(Conflict::new(path, &sf.name, ItemKind::Function, &df.location, sf.location));
+				}
+				continue;
 			}
+			self.functions.push(sf);
 		}
 		for sc in classes.into_iter() {
-			if !self.classes.iter().any(|dc| dc.name == sc.name) {
-				self.classes.push(sc);
+			if let Some(dc) = self.classes.iter().find(|dc| dc.name == sc.name) {
+				if !dc.same_definition(&sc) {
+					conflicts.push(Conflict::new(path, &sc.name.name, ItemKind::Class, &dc.location, sc.location));
+				}
+				continue;
 			}
+			self.classes.push(sc);
+		}
+		for st in templates.into_iter() {
+			if let Some(dt) = self.templates.iter().find(|dt| dt.class.name == st.class.name) {
+				if !dt.same_definition(&st) {
+					conflicts.push(Conflict::new(path, &st.class.name.name, ItemKind::Template, &dt.class.location, st.class.location));
+				}
+				continue;
+			}
+			self.templates.push(st);
 		}
 		for sn in namespaces.into_iter() {
 			if let Some(mut dn) = self.namespaces.iter_mut().find(|dn| dn.name == sn.name) {
-				dn.merge(sn);
+				path.push(sn.name.clone());
+				dn.merge_at(sn, path, conflicts);
+				path.pop();
 				continue;
 			}
 
@@ -218,3 +443,658 @@ impl Namespace {
 
 
 
+impl Conflict {
+	fn new(path: &[String], name: &str, kind: ItemKind, existing: &Option<SourceLocation>, incoming: Option<SourceLocation>) -> Conflict {
+		let mut path = path.to_vec();
+		path.push(name.to_string());
+
+		Conflict{
+			path: path,
+			kind: kind,
+			existing: existing.clone(),
+			incoming: incoming,
+		}
+	}
+}
+
+
+
+impl fmt::Display for Conflict {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		write!(f, "Conflicting {} `{}`", self.kind, self.path.join("::"))?;
+		if let Some(ref incoming) = self.incoming {
+			write!(f, " at {}", incoming)?;
+		}
+		if let Some(ref existing) = self.existing {
+			write!(f, " (first defined at {})", existing)?;
+		}
+
+		Ok(())
+	}
+}
+
+
+
+impl fmt::Display for SourceLocation {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		write!(f, "{}:{}:{}", self.file, self.line, self.column)
+	}
+}
+
+
+
+impl fmt::Display for ItemKind {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		f.write_str(match *self {
+			ItemKind::Namespace => "namespace",
+			ItemKind::Global => "global",
+			ItemKind::Enum => "enum",
+			ItemKind::Variant => "enum variant",
+			ItemKind::Alias => "alias",
+			ItemKind::Function => "function",
+			ItemKind::Class => "class",
+			ItemKind::Base => "base class",
+			ItemKind::Template => "template",
+			ItemKind::Field => "field",
+			ItemKind::Constructor => "constructor",
+			ItemKind::Method => "method",
+		})
+	}
+}
+
+
+
+/// Formats the type roughly as C++ would spell it.
+impl fmt::Display for TypeRef {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		let indirect = match self.kind {
+			TypeKind::Pointer(_) | TypeKind::FunctionPointer{..} => true,
+			_ => false,
+		};
+		if self.is_const && !indirect {
+			f.write_str("const ")?;
+		}
+
+		match self.kind {
+			TypeKind::Void => f.write_str("void")?,
+			TypeKind::Bool => f.write_str("bool")?,
+			TypeKind::Char => f.write_str("char")?,
+			TypeKind::UChar => f.write_str("unsigned char")?,
+			TypeKind::WChar => f.write_str("wchar_t")?,
+			TypeKind::Short => f.write_str("short")?,
+			TypeKind::UShort => f.write_str("unsigned short")?,
+			TypeKind::Int => f.write_str("int")?,
+			TypeKind::UInt => f.write_str("unsigned int")?,
+			TypeKind::Long => f.write_str("long")?,
+			TypeKind::ULong => f.write_str("unsigned long")?,
+			TypeKind::LongLong => f.write_str("long long")?,
+			TypeKind::ULongLong => f.write_str("unsigned long long")?,
+			TypeKind::Float => f.write_str("float")?,
+			TypeKind::Double => f.write_str("double")?,
+			TypeKind::Elaborated(ref path) => for (i, s) in path.iter().enumerate() {
+				if i > 0 {
+					f.write_str("::")?;
+				}
+				write!(f, "{}", s)?;
+			},
+			TypeKind::Pointer(ref t) => write!(f, "{}*", t)?,
+			TypeKind::Reference(ref t) => write!(f, "{}&", t)?,
+			TypeKind::RValueReference(ref t) => write!(f, "{}&&", t)?,
+			TypeKind::Array(Some(size), ref t) => write!(f, "{}[{}]", t, size)?,
+			TypeKind::Array(None, ref t) => write!(f, "{}[]", t)?,
+			TypeKind::FunctionPointer{ref params, ref return_ty, variadic} => {
+				match *return_ty {
+					Some(ref r) => write!(f, "{}", r)?,
+					None => f.write_str("void")?,
+				}
+				f.write_str(" (*)(")?;
+				for (i, p) in params.iter().enumerate() {
+					if i > 0 {
+						f.write_str(", ")?;
+					}
+					write!(f, "{}", p)?;
+				}
+				if variadic {
+					f.write_str(if params.is_empty() { "..." } else { ", ..." })?;
+				}
+				f.write_str(")")?;
+			},
+			TypeKind::TemplateParam(ref name) => f.write_str(name)?,
+			TypeKind::Opaque{ref spelling, ..} => f.write_str(spelling)?,
+		}
+
+		if self.is_const && indirect {
+			f.write_str(" const")?;
+		}
+
+		Ok(())
+	}
+}
+
+
+
+impl fmt::Display for ScopeName {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		f.write_str(&self.name)?;
+		if !self.args.is_empty() {
+			f.write_str("<")?;
+			for (i, a) in self.args.iter().enumerate() {
+				if i > 0 {
+					f.write_str(", ")?;
+				}
+				write!(f, "{}", a)?;
+			}
+			f.write_str(">")?;
+		}
+
+		Ok(())
+	}
+}
+
+
+
+impl fmt::Display for Value {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		match *self {
+			Value::Int(v) => write!(f, "{}", v),
+			Value::UInt(v) => write!(f, "{}", v),
+			Value::Float(v) => write!(f, "{}", v),
+			Value::Double(v) => write!(f, "{}", v),
+			Value::String(ref v) => write!(f, "{:?}", v),
+			Value::Bool(v) => write!(f, "{}", v),
+			Value::Null => f.write_str("nullptr"),
+			Value::EnumVariant{ref path, ref name} => {
+				for p in path.iter() {
+					write!(f, "{}::", p)?;
+				}
+				f.write_str(name)
+			},
+			Value::Construct{ref ty, ref args} => {
+				write!(f, "{}(", ty)?;
+				for (i, a) in args.iter().enumerate() {
+					if i > 0 {
+						f.write_str(", ")?;
+					}
+					write!(f, "{}", a)?;
+				}
+				f.write_str(")")
+			},
+			Value::Raw(ref v) => f.write_str(v),
+		}
+	}
+}
+
+
+
+// Definition comparisons used by `Namespace::merge`. These deliberately
+// ignore source locations and include paths, since the same header can be
+// reached through different paths from different translation units.
+
+impl Var {
+	pub fn same_definition(&self, other: &Var) -> bool {
+		self.name == other.name && self.ty == other.ty
+	}
+}
+
+
+
+impl Enum {
+	pub fn same_definition(&self, other: &Enum) -> bool {
+		self.name == other.name
+			&& self.underlying == other.underlying
+			&& same_items(&self.variants, &other.variants, |a, b| a.name == b.name && a.value == b.value)
+	}
+}
+
+
+
+impl TypeAlias {
+	pub fn same_definition(&self, other: &TypeAlias) -> bool {
+		self.name == other.name && self.ty == other.ty
+	}
+}
+
+
+
+impl Field {
+	pub fn same_definition(&self, other: &Field) -> bool {
+		self.name == other.name
+			&& self.ty == other.ty
+			&& self.access == other.access
+			&& self.is_static == other.is_static
+			&& self.offset == other.offset
+			&& self.bit_width == other.bit_width
+	}
+}
+
+
+
+impl Function {
+	/// Whether `other` declares the same overload, i.e. has the same name,
+	/// parameter types, constness and semantic. Default argument values and
+	/// parameter names are not part of the signature.
+	pub fn same_signature(&self, other: &Function) -> bool {
+		self.name == other.name
+			&& self.semantic == other.semantic
+			&& self.is_const == other.is_const
+			&& same_items(&self.params, &other.params, |a, b| a.ty.same_type(&b.ty))
+	}
+
+
+
+	pub fn same_definition(&self, other: &Function) -> bool {
+		self.same_signature(other)
+			&& self.access == other.access
+			&& self.return_ty == other.return_ty
+	}
+
+
+
+	/// Whether all parameter and return types are fully described.
+	pub fn is_representable(&self) -> bool {
+		self.params.iter().all(|p| p.ty.is_representable())
+			&& self.return_ty.as_ref().map(|r| r.is_representable()).unwrap_or(true)
+	}
+}
+
+
+
+impl Class {
+	pub fn same_definition(&self, other: &Class) -> bool {
+		self.name == other.name
+			&& self.bases == other.bases
+			&& self.is_pod == other.is_pod
+			&& self.is_union == other.is_union
+			&& self.size == other.size
+			&& self.align == other.align
+			&& self.virtual_dtor == other.virtual_dtor
+			&& same_items(&self.enums, &other.enums, Enum::same_definition)
+			&& same_items(&self.aliases, &other.aliases, TypeAlias::same_definition)
+			&& same_items(&self.fields, &other.fields, Field::same_definition)
+			&& same_items(&self.anon_unions, &other.anon_unions, Class::same_definition)
+			&& same_items(&self.ctors, &other.ctors, Function::same_definition)
+			&& same_items(&self.methods, &other.methods, Function::same_definition)
+			&& same_items(&self.classes, &other.classes, Class::same_definition)
+	}
+}
+
+
+
+impl ClassTemplate {
+	pub fn same_definition(&self, other: &ClassTemplate) -> bool {
+		self.params == other.params && self.class.same_definition(&other.class)
+	}
+}
+
+
+
+impl TypeRef {
+	/// Compares two types, ignoring any attached value.
+	pub fn same_type(&self, other: &TypeRef) -> bool {
+		self.kind == other.kind && self.is_const == other.is_const
+	}
+
+
+
+	/// Whether this type refers to a template parameter anywhere inside it.
+	pub fn is_dependent(&self) -> bool {
+		match self.kind {
+			TypeKind::TemplateParam(_) => true,
+			TypeKind::Pointer(ref t) | TypeKind::Reference(ref t) | TypeKind::RValueReference(ref t) | TypeKind::Array(_, ref t) => t.is_dependent(),
+			TypeKind::FunctionPointer{ref params, ref return_ty, ..} => {
+				params.iter().any(|p| p.is_dependent()) || return_ty.as_ref().map(|r| r.is_dependent()).unwrap_or(false)
+			},
+			TypeKind::Elaborated(ref path) => path.iter().any(|s| s.args.iter().any(|a| a.is_dependent())),
+			_ => false,
+		}
+	}
+
+
+
+	/// Whether this type is fully described, i.e. has no `TypeKind::Opaque`
+	/// anywhere inside it.
+	pub fn is_representable(&self) -> bool {
+		match self.kind {
+			TypeKind::Opaque{..} => false,
+			TypeKind::Pointer(ref t) | TypeKind::Reference(ref t) | TypeKind::RValueReference(ref t) | TypeKind::Array(_, ref t) => t.is_representable(),
+			TypeKind::FunctionPointer{ref params, ref return_ty, ..} => {
+				params.iter().all(|p| p.is_representable()) && return_ty.as_ref().map(|r| r.is_representable()).unwrap_or(true)
+			},
+			TypeKind::Elaborated(ref path) => path.iter().all(|s| s.args.iter().all(|a| a.is_representable())),
+			_ => true,
+		}
+	}
+}
+
+
+
+fn same_items<T, F: Fn(&T, &T) -> bool>(a: &[T], b: &[T], f: F) -> bool {
+	a.len() == b.len() && a.iter().zip(b.iter()).all(|(a, b)| f(a, b))
+}
+
+
+
+
+
+
+/// Items with everything optional left out, for tests to build on with
+/// struct update syntax. A new field in the model only needs adding here.
+#[cfg(test)]
+mod fixtures {
+	use super::*;
+
+
+
+	pub fn ty(kind: TypeKind) -> TypeRef {
+		TypeRef{kind: kind, is_const: false, value: None}
+	}
+
+
+
+	pub fn valued(kind: TypeKind, value: Value) -> TypeRef {
+		TypeRef{value: Some(value), ..ty(kind)}
+	}
+
+
+
+	pub fn scope(name: &str) -> ScopeName {
+		ScopeName{name: name.to_string(), args: Vec::new()}
+	}
+
+
+
+	pub fn var(name: &str, ty: TypeRef) -> Var {
+		Var{name: name.to_string(), ty: ty, location: None, doc: None}
+	}
+
+
+
+	/// A public free function with unnamed parameters.
+	pub fn function(name: &str, params: Vec<TypeRef>) -> Function {
+		Function{
+			name: name.to_string(),
+			params: params.into_iter().map(|p| var("", p)).collect(),
+			return_ty: None,
+			semantic: FunctionSemantic::Free,
+			access: Access::Public,
+			is_const: false,
+			location: None,
+			doc: None,
+		}
+	}
+
+
+
+	/// A public non-static field.
+	pub fn field(name: &str, ty: TypeRef) -> Field {
+		Field{
+			name: name.to_string(),
+			ty: ty,
+			access: Access::Public,
+			is_static: false,
+			offset: None,
+			bit_width: None,
+			location: None,
+			doc: None,
+		}
+	}
+
+
+
+	/// An empty class.
+	pub fn class(name: ScopeName) -> Class {
+		Class{
+			include: String::new(),
+			location: None,
+			doc: None,
+			name: name,
+			bases: Vec::new(),
+			is_pod: false,
+			is_union: false,
+			size: None,
+			align: None,
+			enums: Vec::new(),
+			aliases: Vec::new(),
+			fields: Vec::new(),
+			anon_unions: Vec::new(),
+			ctors: Vec::new(),
+			methods: Vec::new(),
+			virtual_dtor: false,
+			classes: Vec::new(),
+		}
+	}
+
+
+
+	pub fn document(root: Namespace) -> ApiDocument {
+		ApiDocument{
+			schema_version: SCHEMA_VERSION,
+			godot_version: None,
+			target: None,
+			defines: Vec::new(),
+			generator_version: String::new(),
+			generated_at: 0,
+			root: root,
+		}
+	}
+
+
+
+	/// A tree with each `TypeKind` and `Value` variant in it exactly once,
+	/// spread over every kind of node that can hold one.
+	pub fn everything() -> Namespace {
+		let mut root = Namespace::new(String::new());
+
+		root.globals.push(var("g", valued(TypeKind::Pointer(Box::new(ty(TypeKind::Void))), Value::String("s".to_string()))));
+		root.enums.push(Enum{
+			name: "E".to_string(),
+			underlying: TypeKind::Int,
+			variants: vec![Variant{name: "A".to_string(), value: Value::Int(0), location: None, doc: None}],
+			location: None,
+			doc: None,
+		});
+		root.aliases.push(TypeAlias{
+			name: ScopeName{name: "A".to_string(), args: vec![ty(TypeKind::Bool)]},
+			ty: ty(TypeKind::Reference(Box::new(ty(TypeKind::Char)))),
+			location: None,
+		});
+		root.functions.push(Function{
+			return_ty: Some(ty(TypeKind::RValueReference(Box::new(ty(TypeKind::WChar))))),
+			..function("f", vec![valued(TypeKind::UChar, Value::UInt(1))])
+		});
+
+		let mut union = class(scope("auto"));
+		union.fields.push(field("u", ty(TypeKind::UShort)));
+		let mut nested = class(scope("N"));
+		nested.fields.push(field("n", valued(TypeKind::ULong, Value::Construct{
+			ty: Box::new(ty(TypeKind::LongLong)),
+			args: vec![Value::Double(0.5)],
+		})));
+		let mut c = class(scope("C"));
+		c.bases.push(Base{ty: ty(TypeKind::Elaborated(vec![scope("B")])), access: Access::Public, is_virtual: false, offset: None});
+		c.enums.push(Enum{
+			name: "F".to_string(),
+			underlying: TypeKind::Bool,
+			variants: vec![Variant{name: "B".to_string(), value: Value::Bool(true), location: None, doc: None}],
+			location: None,
+			doc: None,
+		});
+		c.aliases.push(TypeAlias{name: scope("L"), ty: ty(TypeKind::Long), location: None});
+		c.fields.push(field("x", ty(TypeKind::Array(Some(2), Box::new(ty(TypeKind::Short))))));
+		c.fields.push(field("y", valued(TypeKind::Float, Value::Float(1.0))));
+		c.anon_unions.push(union);
+		c.ctors.push(function("C", Vec::new()));
+		c.methods.push(function("m", vec![valued(TypeKind::UInt, Value::EnumVariant{path: vec!["E".to_string()], name: "A".to_string()})]));
+		c.classes.push(nested);
+		root.classes.push(c);
+
+		let mut pattern = class(scope("T"));
+		pattern.fields.push(field("t", valued(TypeKind::TemplateParam("T".to_string()), Value::Raw("x".to_string()))));
+		root.templates.push(ClassTemplate{
+			params: vec![
+				TemplateParam{name: "T".to_string(), kind: TemplateParamKind::Type},
+				TemplateParam{name: "N".to_string(), kind: TemplateParamKind::Value(ty(TypeKind::ULongLong))},
+				TemplateParam{name: "U".to_string(), kind: TemplateParamKind::Template},
+			],
+			class: pattern,
+		});
+
+		let mut inner = Namespace::new("inner".to_string());
+		inner.globals.push(var("p", valued(TypeKind::FunctionPointer{
+			params: vec![ty(TypeKind::Double)],
+			return_ty: Some(Box::new(ty(TypeKind::Int))),
+			variadic: true,
+		}, Value::Null)));
+		inner.globals.push(var("o", ty(TypeKind::Opaque{spelling: "int A::*".to_string(), size: Some(8), align: Some(8)})));
+		root.namespaces.push(inner);
+
+		root
+	}
+
+
+
+	pub const TYPE_NAMES: &'static [&'static str] = &[
+		"Void", "Bool", "Char", "UChar", "WChar", "Short", "UShort", "Int", "UInt", "Long", "ULong", "LongLong", "ULongLong", "Float", "Double",
+		"Elaborated", "Pointer", "Reference", "RValueReference", "Array", "FunctionPointer", "TemplateParam", "Opaque",
+	];
+
+
+
+	pub fn type_name(kind: &TypeKind) -> &'static str {
+		match *kind {
+			TypeKind::Void => "Void",
+			TypeKind::Bool => "Bool",
+			TypeKind::Char => "Char",
+			TypeKind::UChar => "UChar",
+			TypeKind::WChar => "WChar",
+			TypeKind::Short => "Short",
+			TypeKind::UShort => "UShort",
+			TypeKind::Int => "Int",
+			TypeKind::UInt => "UInt",
+			TypeKind::Long => "Long",
+			TypeKind::ULong => "ULong",
+			TypeKind::LongLong => "LongLong",
+			TypeKind::ULongLong => "ULongLong",
+			TypeKind::Float => "Float",
+			TypeKind::Double => "Double",
+			TypeKind::Elaborated(_) => "Elaborated",
+			TypeKind::Pointer(_) => "Pointer",
+			TypeKind::Reference(_) => "Reference",
+			TypeKind::RValueReference(_) => "RValueReference",
+			TypeKind::Array(..) => "Array",
+			TypeKind::FunctionPointer{..} => "FunctionPointer",
+			TypeKind::TemplateParam(_) => "TemplateParam",
+			TypeKind::Opaque{..} => "Opaque",
+		}
+	}
+
+
+
+	pub const VALUE_NAMES: &'static [&'static str] = &["Int", "UInt", "Float", "Double", "String", "Bool", "Null", "EnumVariant", "Construct", "Raw"];
+
+
+
+	pub fn value_name(value: &Value) -> &'static str {
+		match *value {
+			Value::Int(_) => "Int",
+			Value::UInt(_) => "UInt",
+			Value::Float(_) => "Float",
+			Value::Double(_) => "Double",
+			Value::String(_) => "String",
+			Value::Bool(_) => "Bool",
+			Value::Null => "Null",
+			Value::EnumVariant{..} => "EnumVariant",
+			Value::Construct{..} => "Construct",
+			Value::Raw(_) => "Raw",
+		}
+	}
+}
+
+
+
+#[cfg(test)]
+mod tests {
+	use super::{Namespace, Var, Function, TypeKind, ItemKind, SourceLocation};
+	use super::fixtures::{ty, var, function};
+
+
+
+	fn at(file: &str) -> Option<SourceLocation> {
+		Some(SourceLocation{file: file.to_string(), line: 1, column: 1})
+	}
+
+
+
+	#[test]
+	fn same_definitions_dropped() {
+		let mut dst = Namespace::new(String::new());
+		dst.globals.push(Var{location: at("a.h"), ..var("g", ty(TypeKind::Int))});
+		let mut src = Namespace::new(String::new());
+		src.globals.push(Var{location: at("b.h"), ..var("g", ty(TypeKind::Int))});
+
+		assert!(dst.merge(src).is_empty());
+		assert_eq!(dst.globals.len(), 1);
+		assert_eq!(dst.globals[0].location, at("a.h"));
+	}
+
+
+
+	#[test]
+	fn different_definitions_conflict() {
+		let mut dst = Namespace::new(String::new());
+		dst.globals.push(Var{location: at("a.h"), ..var("g", ty(TypeKind::Int))});
+		let mut src = Namespace::new(String::new());
+		src.globals.push(Var{location: at("b.h"), ..var("g", ty(TypeKind::Float))});
+
+		let conflicts = dst.merge(src);
+		assert_eq!(conflicts.len(), 1);
+		assert_eq!(conflicts[0].path, vec!["g"]);
+		assert_eq!(conflicts[0].kind, ItemKind::Global);
+		assert_eq!(conflicts[0].existing, at("a.h"));
+		assert_eq!(conflicts[0].incoming, at("b.h"));
+		// The existing item wins
+		assert_eq!(dst.globals.len(), 1);
+		assert_eq!(dst.globals[0].ty.kind, TypeKind::Int);
+	}
+
+
+
+	#[test]
+	fn overloads_kept() {
+		let mut dst = Namespace::new(String::new());
+		dst.functions.push(function("f", vec![ty(TypeKind::Int)]));
+		let mut src = Namespace::new(String::new());
+		src.functions.push(function("f", vec![ty(TypeKind::Float)]));
+		src.functions.push(Function{return_ty: Some(ty(TypeKind::Bool)), ..function("f", vec![ty(TypeKind::Int)])});
+
+		let conflicts = dst.merge(src);
+		assert_eq!(dst.functions.len(), 2);
+		// Same signature with a different return type is a conflict, not an
+		// overload
+		assert_eq!(conflicts.len(), 1);
+		assert_eq!(conflicts[0].kind, ItemKind::Function);
+		assert_eq!(conflicts[0].path, vec!["f"]);
+	}
+
+
+
+	#[test]
+	fn nested_conflict_path() {
+		let mut inner = Namespace::new("inner".to_string());
+		inner.globals.push(var("g", ty(TypeKind::Int)));
+		let mut dst = Namespace::new(String::new());
+		dst.namespaces.push(inner);
+
+		let mut inner = Namespace::new("inner".to_string());
+		inner.globals.push(var("g", ty(TypeKind::Long)));
+		inner.globals.push(var("h", ty(TypeKind::Int)));
+		let mut src = Namespace::new(String::new());
+		src.namespaces.push(inner);
+
+		let conflicts = dst.merge(src);
+		assert_eq!(conflicts.len(), 1);
+		assert_eq!(conflicts[0].path, vec!["inner", "g"]);
+		assert_eq!(dst.namespaces.len(), 1);
+		assert_eq!(dst.namespaces[0].globals.len(), 2);
+	}
+}
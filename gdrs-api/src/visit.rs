@@ -0,0 +1,309 @@
+//! Read-only traversal of the API tree.
+//!
+//! Each `visit_*` method defaults to the matching `walk_*` function, which
+//! visits the children of the node. Override a method to act on a node, and
+//! call the `walk_*` function from it to keep descending.
+
+use super::*;
+
+
+
+pub trait Visit<'a> {
+	fn visit_namespace(&mut self, n: &'a Namespace) { walk_namespace(self, n) }
+	fn visit_global(&mut self, g: &'a Var) { walk_var(self, g) }
+	fn visit_enum(&mut self, e: &'a Enum) { walk_enum(self, e) }
+	fn visit_variant(&mut self, v: &'a Variant) { walk_variant(self, v) }
+	fn visit_alias(&mut self, a: &'a TypeAlias) { walk_alias(self, a) }
+	fn visit_class(&mut self, c: &'a Class) { walk_class(self, c) }
+	fn visit_base(&mut self, b: &'a Base) { walk_base(self, b) }
+	fn visit_template(&mut self, t: &'a ClassTemplate) { walk_template(self, t) }
+	fn visit_template_param(&mut self, p: &'a TemplateParam) { walk_template_param(self, p) }
+	fn visit_field(&mut self, f: &'a Field) { walk_field(self, f) }
+	fn visit_function(&mut self, f: &'a Function) { walk_function(self, f) }
+	fn visit_param(&mut self, p: &'a Var) { walk_var(self, p) }
+	fn visit_type(&mut self, t: &'a TypeRef) { walk_type(self, t) }
+	fn visit_scope_name(&mut self, s: &'a ScopeName) { walk_scope_name(self, s) }
+	fn visit_value(&mut self, v: &'a Value) { walk_value(self, v) }
+	/// A qualified name kept as plain strings, such as the enum of a
+	/// `Value::EnumVariant`.
+	fn visit_path(&mut self, _p: &'a [String]) {}
+}
+
+
+
+pub fn walk_namespace<'a, V: Visit<'a> + ?Sized>(v: &mut V, n: &'a Namespace) {
+	let Namespace{name: _, ref globals, ref enums, ref aliases, ref functions, ref classes, ref templates, ref namespaces} = *n;
+
+	for g in globals.iter() {
+		v.visit_global(g);
+	}
+	for e in enums.iter() {
+		v.visit_enum(e);
+	}
+	for a in aliases.iter() {
+		v.visit_alias(a);
+	}
+	for f in functions.iter() {
+		v.visit_function(f);
+	}
+	for c in classes.iter() {
+		v.visit_class(c);
+	}
+	for t in templates.iter() {
+		v.visit_template(t);
+	}
+	for n in namespaces.iter() {
+		v.visit_namespace(n);
+	}
+}
+
+
+
+pub fn walk_var<'a, V: Visit<'a> + ?Sized>(v: &mut V, var: &'a Var) {
+	let Var{name: _, ref ty, location: _, doc: _} = *var;
+
+	v.visit_type(ty);
+}
+
+
+
+pub fn walk_enum<'a, V: Visit<'a> + ?Sized>(v: &mut V, e: &'a Enum) {
+	let Enum{name: _, underlying: _, ref variants, location: _, doc: _} = *e;
+
+	for variant in variants.iter() {
+		v.visit_variant(variant);
+	}
+}
+
+
+
+pub fn walk_variant<'a, V: Visit<'a> + ?Sized>(v: &mut V, variant: &'a Variant) {
+	let Variant{name: _, ref value, location: _, doc: _} = *variant;
+
+	v.visit_value(value);
+}
+
+
+
+pub fn walk_alias<'a, V: Visit<'a> + ?Sized>(v: &mut V, a: &'a TypeAlias) {
+	let TypeAlias{ref name, ref ty, location: _} = *a;
+
+	v.visit_scope_name(name);
+	v.visit_type(ty);
+}
+
+
+
+pub fn walk_class<'a, V: Visit<'a> + ?Sized>(v: &mut V, c: &'a Class) {
+	let Class{
+		include: _,
+		location: _,
+		doc: _,
+		ref name,
+		ref bases,
+		is_pod: _,
+		is_union: _,
+		size: _,
+		align: _,
+		ref enums,
+		ref aliases,
+		ref fields,
+		ref anon_unions,
+		ref ctors,
+		ref methods,
+		virtual_dtor: _,
+		ref classes,
+	} = *c;
+
+	v.visit_scope_name(name);
+	for b in bases.iter() {
+		v.visit_base(b);
+	}
+	for e in enums.iter() {
+		v.visit_enum(e);
+	}
+	for a in aliases.iter() {
+		v.visit_alias(a);
+	}
+	for f in fields.iter() {
+		v.visit_field(f);
+	}
+	for u in anon_unions.iter() {
+		v.visit_class(u);
+	}
+	for f in ctors.iter() {
+		v.visit_function(f);
+	}
+	for f in methods.iter() {
+		v.visit_function(f);
+	}
+	for c in classes.iter() {
+		v.visit_class(c);
+	}
+}
+
+
+
+pub fn walk_base<'a, V: Visit<'a> + ?Sized>(v: &mut V, b: &'a Base) {
+	let Base{ref ty, access: _, is_virtual: _, offset: _} = *b;
+
+	v.visit_type(ty);
+}
+
+
+
+pub fn walk_template<'a, V: Visit<'a> + ?Sized>(v: &mut V, t: &'a ClassTemplate) {
+	let ClassTemplate{ref params, ref class} = *t;
+
+	for p in params.iter() {
+		v.visit_template_param(p);
+	}
+	v.visit_class(class);
+}
+
+
+
+pub fn walk_template_param<'a, V: Visit<'a> + ?Sized>(v: &mut V, p: &'a TemplateParam) {
+	let TemplateParam{name: _, ref kind} = *p;
+
+	match *kind {
+		TemplateParamKind::Value(ref ty) => v.visit_type(ty),
+		TemplateParamKind::Type | TemplateParamKind::Template => (),
+	}
+}
+
+
+
+pub fn walk_field<'a, V: Visit<'a> + ?Sized>(v: &mut V, f: &'a Field) {
+	let Field{name: _, ref ty, access: _, is_static: _, offset: _, bit_width: _, location: _, doc: _} = *f;
+
+	v.visit_type(ty);
+}
+
+
+
+pub fn walk_function<'a, V: Visit<'a> + ?Sized>(v: &mut V, f: &'a Function) {
+	let Function{name: _, ref params, ref return_ty, semantic: _, access: _, is_const: _, location: _, doc: _} = *f;
+
+	for p in params.iter() {
+		v.visit_param(p);
+	}
+	if let Some(ref r) = *return_ty {
+		v.visit_type(r);
+	}
+}
+
+
+
+pub fn walk_type<'a, V: Visit<'a> + ?Sized>(v: &mut V, t: &'a TypeRef) {
+	let TypeRef{ref kind, is_const: _, ref value} = *t;
+
+	match *kind {
+		TypeKind::Elaborated(ref path) => for s in path.iter() {
+			v.visit_scope_name(s);
+		},
+		TypeKind::Pointer(ref t)
+		| TypeKind::Reference(ref t)
+		| TypeKind::RValueReference(ref t)
+		| TypeKind::Array(_, ref t)
+		=> v.visit_type(t),
+		TypeKind::FunctionPointer{ref params, ref return_ty, variadic: _} => {
+			for p in params.iter() {
+				v.visit_type(p);
+			}
+			if let Some(ref r) = *return_ty {
+				v.visit_type(r);
+			}
+		},
+		TypeKind::Void | TypeKind::Bool | TypeKind::Char | TypeKind::UChar | TypeKind::WChar
+		| TypeKind::Short | TypeKind::UShort | TypeKind::Int | TypeKind::UInt | TypeKind::Long | TypeKind::ULong
+		| TypeKind::LongLong | TypeKind::ULongLong | TypeKind::Float | TypeKind::Double
+		| TypeKind::TemplateParam(_) | TypeKind::Opaque{..}
+		=> (),
+	}
+	if let Some(ref value) = *value {
+		v.visit_value(value);
+	}
+}
+
+
+
+pub fn walk_value<'a, V: Visit<'a> + ?Sized>(v: &mut V, value: &'a Value) {
+	match *value {
+		Value::Construct{ref ty, ref args} => {
+			v.visit_type(ty);
+			for a in args.iter() {
+				v.visit_value(a);
+			}
+		},
+		Value::EnumVariant{ref path, ..} => v.visit_path(path),
+		Value::Int(_) | Value::UInt(_) | Value::Float(_) | Value::Double(_) | Value::String(_)
+		| Value::Bool(_) | Value::Null | Value::Raw(_)
+		=> (),
+	}
+}
+
+
+
+pub fn walk_scope_name<'a, V: Visit<'a> + ?Sized>(v: &mut V, s: &'a ScopeName) {
+	let ScopeName{name: _, ref args} = *s;
+
+	for a in args.iter() {
+		v.visit_type(a);
+	}
+}
+
+
+
+#[cfg(test)]
+mod tests {
+	use std::collections::BTreeMap;
+	use super::{Visit, walk_type, walk_value};
+	use super::super::{TypeRef, Value};
+	use super::super::fixtures::{everything, type_name, value_name, TYPE_NAMES, VALUE_NAMES};
+
+
+
+	/// Counts the types and values reached, by variant.
+	#[derive(Default)]
+	struct Counter {
+		types: BTreeMap<&'static str, usize>,
+		values: BTreeMap<&'static str, usize>,
+		paths: Vec<Vec<String>>,
+	}
+
+
+
+	impl<'a> Visit<'a> for Counter {
+		fn visit_type(&mut self, t: &'a TypeRef) {
+			*self.types.entry(type_name(&t.kind)).or_insert(0) += 1;
+			walk_type(self, t)
+		}
+
+
+
+		fn visit_value(&mut self, v: &'a Value) {
+			*self.values.entry(value_name(v)).or_insert(0) += 1;
+			walk_value(self, v)
+		}
+
+
+
+		fn visit_path(&mut self, p: &'a [String]) {
+			self.paths.push(p.to_vec());
+		}
+	}
+
+
+
+	#[test]
+	fn every_variant_visited() {
+		let root = everything();
+		let mut counter = Counter::default();
+		counter.visit_namespace(&root);
+
+		assert_eq!(counter.types, TYPE_NAMES.iter().map(|n| (*n, 1)).collect());
+		assert_eq!(counter.values, VALUE_NAMES.iter().map(|n| (*n, 1)).collect());
+		assert_eq!(counter.paths, vec![vec!["E".to_string()]]);
+	}
+}
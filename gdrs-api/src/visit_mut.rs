@@ -0,0 +1,312 @@
+//! In-place traversal of the API tree, mirroring `visit`.
+//!
+//! Each `visit_*` method defaults to the matching `walk_*_mut` function,
+//! which visits the children of the node. Override a method to act on a
+//! node, and call the `walk_*_mut` function from it to keep descending.
+
+use super::*;
+
+
+
+pub trait VisitMut {
+	fn visit_namespace(&mut self, n: &mut Namespace) { walk_namespace_mut(self, n) }
+	fn visit_global(&mut self, g: &mut Var) { walk_var_mut(self, g) }
+	fn visit_enum(&mut self, e: &mut Enum) { walk_enum_mut(self, e) }
+	fn visit_variant(&mut self, v: &mut Variant) { walk_variant_mut(self, v) }
+	fn visit_alias(&mut self, a: &mut TypeAlias) { walk_alias_mut(self, a) }
+	fn visit_class(&mut self, c: &mut Class) { walk_class_mut(self, c) }
+	fn visit_base(&mut self, b: &mut Base) { walk_base_mut(self, b) }
+	fn visit_template(&mut self, t: &mut ClassTemplate) { walk_template_mut(self, t) }
+	fn visit_template_param(&mut self, p: &mut TemplateParam) { walk_template_param_mut(self, p) }
+	fn visit_field(&mut self, f: &mut Field) { walk_field_mut(self, f) }
+	fn visit_function(&mut self, f: &mut Function) { walk_function_mut(self, f) }
+	fn visit_param(&mut self, p: &mut Var) { walk_var_mut(self, p) }
+	fn visit_type(&mut self, t: &mut TypeRef) { walk_type_mut(self, t) }
+	fn visit_scope_name(&mut self, s: &mut ScopeName) { walk_scope_name_mut(self, s) }
+	fn visit_value(&mut self, v: &mut Value) { walk_value_mut(self, v) }
+	/// A qualified name kept as plain strings, such as the enum of a
+	/// `Value::EnumVariant`.
+	fn visit_path(&mut self, _p: &mut Vec<String>) {}
+}
+
+
+
+pub fn walk_namespace_mut<V: VisitMut + ?Sized>(v: &mut V, n: &mut Namespace) {
+	let Namespace{name: _, ref mut globals, ref mut enums, ref mut aliases, ref mut functions, ref mut classes, ref mut templates, ref mut namespaces} = *n;
+
+	for g in globals.iter_mut() {
+		v.visit_global(g);
+	}
+	for e in enums.iter_mut() {
+		v.visit_enum(e);
+	}
+	for a in aliases.iter_mut() {
+		v.visit_alias(a);
+	}
+	for f in functions.iter_mut() {
+		v.visit_function(f);
+	}
+	for c in classes.iter_mut() {
+		v.visit_class(c);
+	}
+	for t in templates.iter_mut() {
+		v.visit_template(t);
+	}
+	for n in namespaces.iter_mut() {
+		v.visit_namespace(n);
+	}
+}
+
+
+
+pub fn walk_var_mut<V: VisitMut + ?Sized>(v: &mut V, var: &mut Var) {
+	let Var{name: _, ref mut ty, location: _, doc: _} = *var;
+
+	v.visit_type(ty);
+}
+
+
+
+pub fn walk_enum_mut<V: VisitMut + ?Sized>(v: &mut V, e: &mut Enum) {
+	let Enum{name: _, underlying: _, ref mut variants, location: _, doc: _} = *e;
+
+	for variant in variants.iter_mut() {
+		v.visit_variant(variant);
+	}
+}
+
+
+
+pub fn walk_variant_mut<V: VisitMut + ?Sized>(v: &mut V, variant: &mut Variant) {
+	let Variant{name: _, ref mut value, location: _, doc: _} = *variant;
+
+	v.visit_value(value);
+}
+
+
+
+pub fn walk_alias_mut<V: VisitMut + ?Sized>(v: &mut V, a: &mut TypeAlias) {
+	let TypeAlias{ref mut name, ref mut ty, location: _} = *a;
+
+	v.visit_scope_name(name);
+	v.visit_type(ty);
+}
+
+
+
+pub fn walk_class_mut<V: VisitMut + ?Sized>(v: &mut V, c: &mut Class) {
+	let Class{
+		include: _,
+		location: _,
+		doc: _,
+		ref mut name,
+		ref mut bases,
+		is_pod: _,
+		is_union: _,
+		size: _,
+		align: _,
+		ref mut enums,
+		ref mut aliases,
+		ref mut fields,
+		ref mut anon_unions,
+		ref mut ctors,
+		ref mut methods,
+		virtual_dtor: _,
+		ref mut classes,
+	} = *c;
+
+	v.visit_scope_name(name);
+	for b in bases.iter_mut() {
+		v.visit_base(b);
+	}
+	for e in enums.iter_mut() {
+		v.visit_enum(e);
+	}
+	for a in aliases.iter_mut() {
+		v.visit_alias(a);
+	}
+	for f in fields.iter_mut() {
+		v.visit_field(f);
+	}
+	for u in anon_unions.iter_mut() {
+		v.visit_class(u);
+	}
+	for f in ctors.iter_mut() {
+		v.visit_function(f);
+	}
+	for f in methods.iter_mut() {
+		v.visit_function(f);
+	}
+	for c in classes.iter_mut() {
+		v.visit_class(c);
+	}
+}
+
+
+
+pub fn walk_base_mut<V: VisitMut + ?Sized>(v: &mut V, b: &mut Base) {
+	let Base{ref mut ty, access: _, is_virtual: _, offset: _} = *b;
+
+	v.visit_type(ty);
+}
+
+
+
+pub fn walk_template_mut<V: VisitMut + ?Sized>(v: &mut V, t: &mut ClassTemplate) {
+	let ClassTemplate{ref mut params, ref mut class} = *t;
+
+	for p in params.iter_mut() {
+		v.visit_template_param(p);
+	}
+	v.visit_class(class);
+}
+
+
+
+pub fn walk_template_param_mut<V: VisitMut + ?Sized>(v: &mut V, p: &mut TemplateParam) {
+	let TemplateParam{name: _, ref mut kind} = *p;
+
+	match *kind {
+		TemplateParamKind::Value(ref mut ty) => v.visit_type(ty),
+		TemplateParamKind::Type | TemplateParamKind::Template => (),
+	}
+}
+
+
+
+pub fn walk_field_mut<V: VisitMut + ?Sized>(v: &mut V, f: &mut Field) {
+	let Field{name: _, ref mut ty, access: _, is_static: _, offset: _, bit_width: _, location: _, doc: _} = *f;
+
+	v.visit_type(ty);
+}
+
+
+
+pub fn walk_function_mut<V: VisitMut + ?Sized>(v: &mut V, f: &mut Function) {
+	let Function{name: _, ref mut params, ref mut return_ty, semantic: _, access: _, is_const: _, location: _, doc: _} = *f;
+
+	for p in params.iter_mut() {
+		v.visit_param(p);
+	}
+	if let Some(ref mut r) = *return_ty {
+		v.visit_type(r);
+	}
+}
+
+
+
+pub fn walk_type_mut<V: VisitMut + ?Sized>(v: &mut V, t: &mut TypeRef) {
+	let TypeRef{ref mut kind, is_const: _, ref mut value} = *t;
+
+	match *kind {
+		TypeKind::Elaborated(ref mut path) => for s in path.iter_mut() {
+			v.visit_scope_name(s);
+		},
+		TypeKind::Pointer(ref mut t)
+		| TypeKind::Reference(ref mut t)
+		| TypeKind::RValueReference(ref mut t)
+		| TypeKind::Array(_, ref mut t)
+		=> v.visit_type(t),
+		TypeKind::FunctionPointer{ref mut params, ref mut return_ty, variadic: _} => {
+			for p in params.iter_mut() {
+				v.visit_type(p);
+			}
+			if let Some(ref mut r) = *return_ty {
+				v.visit_type(r);
+			}
+		},
+		TypeKind::Void | TypeKind::Bool | TypeKind::Char | TypeKind::UChar | TypeKind::WChar
+		| TypeKind::Short | TypeKind::UShort | TypeKind::Int | TypeKind::UInt | TypeKind::Long | TypeKind::ULong
+		| TypeKind::LongLong | TypeKind::ULongLong | TypeKind::Float | TypeKind::Double
+		| TypeKind::TemplateParam(_) | TypeKind::Opaque{..}
+		=> (),
+	}
+	if let Some(ref mut value) = *value {
+		v.visit_value(value);
+	}
+}
+
+
+
+pub fn walk_value_mut<V: VisitMut + ?Sized>(v: &mut V, value: &mut Value) {
+	match *value {
+		Value::Construct{ref mut ty, ref mut args} => {
+			v.visit_type(ty);
+			for a in args.iter_mut() {
+				v.visit_value(a);
+			}
+		},
+		Value::EnumVariant{ref mut path, ..} => v.visit_path(path),
+		Value::Int(_) | Value::UInt(_) | Value::Float(_) | Value::Double(_) | Value::String(_)
+		| Value::Bool(_) | Value::Null | Value::Raw(_)
+		=> (),
+	}
+}
+
+
+
+pub fn walk_scope_name_mut<V: VisitMut + ?Sized>(v: &mut V, s: &mut ScopeName) {
+	let ScopeName{name: _, ref mut args} = *s;
+
+	for a in args.iter_mut() {
+		v.visit_type(a);
+	}
+}
+
+
+
+#[cfg(test)]
+mod tests {
+	use std::collections::BTreeMap;
+	use super::{VisitMut, walk_type_mut, walk_value_mut};
+	use super::super::{TypeRef, Value};
+	use super::super::fixtures::{everything, type_name, value_name, TYPE_NAMES, VALUE_NAMES};
+
+
+
+	/// Counts the types and values reached, by variant, and qualifies every
+	/// path with `ns`.
+	#[derive(Default)]
+	struct Counter {
+		types: BTreeMap<&'static str, usize>,
+		values: BTreeMap<&'static str, usize>,
+	}
+
+
+
+	impl VisitMut for Counter {
+		fn visit_type(&mut self, t: &mut TypeRef) {
+			*self.types.entry(type_name(&t.kind)).or_insert(0) += 1;
+			walk_type_mut(self, t)
+		}
+
+
+
+		fn visit_value(&mut self, v: &mut Value) {
+			*self.values.entry(value_name(v)).or_insert(0) += 1;
+			walk_value_mut(self, v)
+		}
+
+
+
+		fn visit_path(&mut self, p: &mut Vec<String>) {
+			p.insert(0, "ns".to_string());
+		}
+	}
+
+
+
+	#[test]
+	fn every_variant_visited() {
+		let mut root = everything();
+		let mut counter = Counter::default();
+		counter.visit_namespace(&mut root);
+
+		assert_eq!(counter.types, TYPE_NAMES.iter().map(|n| (*n, 1)).collect());
+		assert_eq!(counter.values, VALUE_NAMES.iter().map(|n| (*n, 1)).collect());
+		assert_eq!(root.classes[0].methods[0].params[0].ty.value, Some(Value::EnumVariant{
+			path: vec!["ns".to_string(), "E".to_string()],
+			name: "A".to_string(),
+		}));
+	}
+}
@@ -0,0 +1,147 @@
+//! On-disk cache of parsed translation units. An entry is reused as long as
+//! the file, every header it included, the flags it was parsed with and the
+//! gdrs-parse binary itself are unchanged.
+
+use std::collections::HashMap;
+use std::collections::hash_map::DefaultHasher;
+use std::env;
+use std::fs;
+use std::hash::{Hash, Hasher};
+use std::io::{self, Read};
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use bincode::{self, SizeLimit};
+
+use gdrs_api;
+use super::Unit;
+
+
+
+pub struct Cache {
+	dir: PathBuf,
+	/// Hash of the running executable, since the version number doesn't
+	/// change with every rebuild.
+	generator: u64,
+	/// Content hashes of the files checked so far. Most headers are shared by
+	/// many translation units, so each is only read once per run.
+	hashes: Mutex<HashMap<PathBuf, Option<u64>>>,
+}
+
+
+
+#[derive(Serialize, Deserialize)]
+struct Entry {
+	schema_version: u32,
+	generator_version: String,
+	generator: u64,
+	/// Every file the translation unit read, with the hash of its contents.
+	inputs: Vec<(PathBuf, u64)>,
+	/// Spellings of `#include`s that didn't resolve to a file. Creating one
+	/// of them could change the result, so entries with any are never reused.
+	unresolved: Vec<String>,
+	unit: Unit,
+}
+
+
+
+impl Cache {
+	pub fn open<P: AsRef<Path>>(dir: P) -> io::Result<Cache> {
+		fs::create_dir_all(dir.as_ref())?;
+
+		Ok(Cache{
+			dir: dir.as_ref().to_path_buf(),
+			generator: hash_file(&env::current_exe()?)?,
+			hashes: Mutex::new(HashMap::new()),
+		})
+	}
+
+
+
+	/// The cached result for `file`, if none of its inputs have changed.
+	/// `contents` is given for generated files that only exist in memory.
+	pub fn load(&self, file: &str, contents: Option<&str>, flags: &[String]) -> Option<Unit> {
+		let entry: Entry = match fs::File::open(self.entry_path(file, contents, flags)).ok()
+			.and_then(|f| bincode::serde::deserialize_from(&mut io::BufReader::new(f), SizeLimit::Infinite).ok())
+		{
+			Some(entry) => entry,
+			None => return None,
+		};
+
+		if entry.schema_version != gdrs_api::SCHEMA_VERSION || entry.generator_version != env!("CARGO_PKG_VERSION") || entry.generator != self.generator {
+			return None;
+		}
+		if !entry.unresolved.is_empty() {
+			return None;
+		}
+		if entry.inputs.iter().any(|&(ref path, hash)| self.hash(path) != Some(hash)) {
+			return None;
+		}
+
+		Some(entry.unit)
+	}
+
+
+
+	/// Stores the result for `file`, which read the files in `inputs` and
+	/// failed to find the includes in `unresolved`.
+	pub fn store(&self, file: &str, contents: Option<&str>, flags: &[String], inputs: Vec<PathBuf>, unresolved: Vec<String>, unit: &Unit) -> Result<(), String> {
+		let mut hashed = Vec::with_capacity(inputs.len());
+		for path in inputs.into_iter() {
+			match self.hash(&path) {
+				Some(hash) => hashed.push((path, hash)),
+				None => return Err(format!("can't read `{}`", path.display())),
+			}
+		}
+
+		let entry = Entry{
+			schema_version: gdrs_api::SCHEMA_VERSION,
+			generator_version: env!("CARGO_PKG_VERSION").to_string(),
+			generator: self.generator,
+			inputs: hashed,
+			unresolved: unresolved,
+			unit: unit.clone(),
+		};
+
+		// Written aside and renamed so a concurrent or interrupted run never
+		// sees half an entry
+		let path = self.entry_path(file, contents, flags);
+		let temp = path.with_extension("tmp");
+		fs::File::create(&temp)
+			.map_err(|e| e.to_string())
+			.and_then(|f| bincode::serde::serialize_into(&mut io::BufWriter::new(f), &entry, SizeLimit::Infinite).map_err(|e| e.to_string()))
+			.and_then(|_| fs::rename(&temp, &path).map_err(|e| e.to_string()))
+	}
+
+
+
+	fn entry_path(&self, file: &str, contents: Option<&str>, flags: &[String]) -> PathBuf {
+		let mut hasher = DefaultHasher::new();
+		file.hash(&mut hasher);
+		contents.hash(&mut hasher);
+		flags.hash(&mut hasher);
+		self.dir.join(format!("{:016x}.bin", hasher.finish()))
+	}
+
+
+
+	fn hash(&self, path: &Path) -> Option<u64> {
+		if let Some(hash) = self.hashes.lock().unwrap().get(path) {
+			return *hash;
+		}
+
+		let hash = hash_file(path).ok();
+		self.hashes.lock().unwrap().insert(path.to_path_buf(), hash);
+		hash
+	}
+}
+
+
+
+fn hash_file(path: &Path) -> io::Result<u64> {
+	let mut contents = Vec::new();
+	fs::File::open(path)?.read_to_end(&mut contents)?;
+
+	let mut hasher = DefaultHasher::new();
+	contents.hash(&mut hasher);
+	Ok(hasher.finish())
+}
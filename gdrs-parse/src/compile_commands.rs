@@ -0,0 +1,273 @@
+//! Per-file flags from a JSON compilation database, the
+//! `compile_commands.json` written by CMake, Ninja or Bear.
+
+use std::collections::HashMap;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use serde_json;
+
+
+
+/// Options that change what the parser sees and take the next argument as
+/// their value. They are matched by their exact name, so `-include-pch` is
+/// not mistaken for `-include`.
+const VALUE_OPTIONS: &'static [&'static str] = &[
+	"-D", "-U", "-I",
+	"-isystem", "-iquote", "-idirafter", "-isysroot", "--sysroot",
+	"-iprefix", "-iwithprefix", "-iwithprefixbefore",
+	"-include", "-imacros",
+	"-target", "-x",
+];
+
+/// Of `VALUE_OPTIONS`, the ones that may also have their value joined on,
+/// as in `-DNAME`, `-isystemDIR` or `--sysroot=DIR`. Longer names come
+/// first where one starts with another.
+const JOINED_OPTIONS: &'static [&'static str] = &[
+	"-D", "-U", "-I",
+	"-isystem", "-iquote", "-idirafter", "-isysroot", "--sysroot=",
+	"-iprefix", "-iwithprefixbefore", "-iwithprefix",
+	"-include", "-imacros",
+	"-x",
+];
+
+/// Other options that start like one of `JOINED_OPTIONS`, and so must not
+/// be read as it with a joined value.
+const LOOKALIKES: &'static [&'static str] = &["-include-pch", "-isystem-after"];
+
+/// Of `VALUE_OPTIONS`, the ones whose value is a path relative to the
+/// directory the command ran in.
+const PATH_OPTIONS: &'static [&'static str] = &["-I", "-isystem", "-iquote", "-idirafter", "-isysroot", "--sysroot", "-iprefix", "-include", "-imacros"];
+
+/// Options without a value that change what the parser sees. Anything not
+/// listed here or in `VALUE_OPTIONS` (optimization, warnings, outputs and
+/// so on) is dropped.
+const FLAGS: &'static [&'static str] = &[
+	"-m32", "-m64",
+	"-nostdinc", "-nostdinc++",
+	"-fno-exceptions", "-fexceptions", "-fno-rtti", "-frtti",
+	"-funsigned-char", "-fsigned-char", "-fshort-wchar",
+	"-pthread",
+];
+
+
+
+#[derive(Deserialize)]
+struct Command {
+	directory: String,
+	file: String,
+	#[serde(default)]
+	arguments: Option<Vec<String>>,
+	#[serde(default)]
+	command: Option<String>,
+}
+
+
+
+/// Relevant flags for each file in the database, by canonical path.
+pub struct Database {
+	files: HashMap<PathBuf, Vec<String>>,
+}
+
+
+
+impl Database {
+	pub fn load<P: AsRef<Path>>(path: P) -> Result<Database, String> {
+		let file = fs::File::open(path.as_ref()).map_err(|e| e.to_string())?;
+		let commands: Vec<Command> = serde_json::from_reader(io::BufReader::new(file)).map_err(|e| e.to_string())?;
+
+		let mut files = HashMap::with_capacity(commands.len());
+		for c in commands.into_iter() {
+			let dir = PathBuf::from(c.directory);
+			let args = match (c.arguments, c.command) {
+				(Some(args), _) => args,
+				(None, Some(command)) => split_command(&command),
+				(None, None) => return Err(format!("no command for `{}`", c.file)),
+			};
+
+			// The first argument is the compiler itself
+			files.insert(canonical(&dir.join(&c.file)), filter_args(args.iter().skip(1), &dir));
+		}
+
+		Ok(Database{files: files})
+	}
+
+
+
+	pub fn flags<P: AsRef<Path>>(&self, file: P) -> Option<&[String]> {
+		self.files.get(&canonical(file.as_ref())).map(|f| &f[..])
+	}
+
+
+
+	/// Every file in the database, sorted.
+	pub fn files(&self) -> Vec<String> {
+		let mut files = self.files.keys().map(|f| f.to_string_lossy().into_owned()).collect::<Vec<_>>();
+		files.sort();
+		files
+	}
+}
+
+
+
+fn canonical(path: &Path) -> PathBuf {
+	fs::canonicalize(path).unwrap_or_else(|_| path.to_path_buf())
+}
+
+
+
+fn filter_args<'a, I: Iterator<Item=&'a String>>(mut args: I, dir: &Path) -> Vec<String> {
+	let mut kept = Vec::new();
+	while let Some(arg) = args.next() {
+		let (option, value) = if let Some(option) = VALUE_OPTIONS.iter().cloned().find(|o| **o == arg[..]) {
+			match args.next() {
+				Some(value) => (option, value.clone()),
+				None => break,
+			}
+		} else if let Some(joined) = JOINED_OPTIONS.iter().cloned().find(|o| arg.starts_with(o) && arg.len() > o.len() && !LOOKALIKES.contains(&&arg[..])) {
+			(joined.trim_right_matches('='), arg[joined.len()..].to_string())
+		} else {
+			if FLAGS.contains(&&arg[..]) || arg.starts_with("-std=") || arg.starts_with("--target=") {
+				kept.push(arg.clone());
+			}
+			continue;
+		};
+
+		let value = if PATH_OPTIONS.contains(&option) { dir.join(value).to_string_lossy().into_owned() } else { value };
+		kept.push(option.to_string());
+		kept.push(value);
+	}
+
+	kept
+}
+
+
+
+/// Splits a `command` string the way a POSIX shell would, minus expansions.
+pub fn split_command(command: &str) -> Vec<String> {
+	let mut args = Vec::new();
+	let mut arg = String::new();
+	// Set once anything, even an empty pair of quotes, starts an argument
+	let mut started = false;
+	let mut chars = command.chars();
+
+	while let Some(c) = chars.next() {
+		match c {
+			' ' | '\t' | '\n' => {
+				if started {
+					args.push(arg.clone());
+					arg.clear();
+					started = false;
+				}
+				continue;
+			},
+			'\'' => while let Some(c) = chars.next() {
+				if c == '\'' {
+					break;
+				}
+				arg.push(c);
+			},
+			'"' => while let Some(c) = chars.next() {
+				match c {
+					'"' => break,
+					'\\' => match chars.next() {
+						Some(e @ '"') | Some(e @ '\\') | Some(e @ '$') | Some(e @ '`') => arg.push(e),
+						Some(e) => {
+							arg.push('\\');
+							arg.push(e);
+						},
+						None => arg.push('\\'),
+					},
+					c => arg.push(c),
+				}
+			},
+			'\\' => if let Some(e) = chars.next() {
+				arg.push(e);
+			},
+			c => arg.push(c),
+		}
+		started = true;
+	}
+	if started {
+		args.push(arg);
+	}
+
+	args
+}
+
+
+
+#[cfg(test)]
+mod tests {
+	use std::path::Path;
+	use super::{split_command, filter_args};
+
+
+
+	fn filter(args: &[&str]) -> Vec<String> {
+		let args = args.iter().map(|a| a.to_string()).collect::<Vec<_>>();
+		filter_args(args.iter(), Path::new("/build"))
+	}
+
+
+
+	#[test]
+	fn split_plain() {
+		assert_eq!(split_command("  c++ -c\tfoo.cpp \n"), vec!["c++", "-c", "foo.cpp"]);
+	}
+
+
+
+	#[test]
+	fn split_quotes() {
+		assert_eq!(split_command("c++ '-DNAME=\"a b\"' \"-I dir\" '' x"), vec!["c++", "-DNAME=\"a b\"", "-I dir", "", "x"]);
+		assert_eq!(split_command("a'b'\"c\"d"), vec!["abcd"]);
+	}
+
+
+
+	#[test]
+	fn split_escapes() {
+		assert_eq!(split_command("-DA=\\\"x\\\" a\\ b"), vec!["-DA=\"x\"", "a b"]);
+		assert_eq!(split_command("\"\\\" \\$ \\n\""), vec!["\" $ \\n"]);
+		assert_eq!(split_command("'\\n'"), vec!["\\n"]);
+	}
+
+
+
+	#[test]
+	fn joined_and_separate_values() {
+		assert_eq!(filter(&["-DA=1", "-D", "B", "-Iinc", "-I", "/abs"]), vec!["-D", "A=1", "-D", "B", "-I", "/build/inc", "-I", "/abs"]);
+		assert_eq!(filter(&["-isystem", "sys", "--sysroot=/root", "-target", "x86_64-linux-gnu"]), vec!["-isystem", "/build/sys", "--sysroot", "/root", "-target", "x86_64-linux-gnu"]);
+	}
+
+
+
+	#[test]
+	fn exact_option_names() {
+		assert_eq!(filter(&["-include-pch", "pch.h", "-include", "config.h"]), vec!["-include", "/build/config.h"]);
+		assert_eq!(filter(&["-isystem-after", "after", "-imacros", "m.h"]), vec!["-imacros", "/build/m.h"]);
+	}
+
+
+
+	#[test]
+	fn joined_include_options() {
+		assert_eq!(filter(&["-isystemfoo", "-iquote/abs", "-idirafterlast"]), vec!["-isystem", "/build/foo", "-iquote", "/abs", "-idirafter", "/build/last"]);
+		assert_eq!(filter(&["-includeconfig.h", "-iwithprefixbeforeinc"]), vec!["-include", "/build/config.h", "-iwithprefixbefore", "inc"]);
+	}
+
+
+
+	#[test]
+	fn language_kept() {
+		assert_eq!(filter(&["-xc", "-x", "c++-header"]), vec!["-x", "c", "-x", "c++-header"]);
+	}
+
+
+
+	#[test]
+	fn irrelevant_options_dropped() {
+		assert_eq!(filter(&["-O2", "-Wall", "-c", "foo.cpp", "-o", "foo.o", "-std=c++11", "-m32", "-fno-rtti"]), vec!["-std=c++11", "-m32", "-fno-rtti"]);
+	}
+}
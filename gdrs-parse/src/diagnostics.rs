@@ -0,0 +1,149 @@
+use std::fmt;
+use std::io::{self, Write};
+use std::str::FromStr;
+use serde_json;
+
+use gdrs_api::SourceLocation;
+
+
+
+/// What kind of problem a diagnostic reports. Each category can be made
+/// fatal with `--deny`, by the name it is serialized as.
+#[derive(Copy, Clone, PartialEq, Eq, Hash, PartialOrd, Ord, Debug, Serialize, Deserialize)]
+pub enum Category {
+	/// A type that was recorded as `TypeKind::Opaque`.
+	#[serde(rename = "unsupported-type")]
+	UnsupportedType,
+	#[serde(rename = "unsupported-template-param")]
+	UnsupportedTemplateParam,
+	#[serde(rename = "private-pod-field")]
+	PrivatePodField,
+	/// Two translation units disagree about the definition of an item.
+	#[serde(rename = "conflict")]
+	Conflict,
+	/// A declaration that was left out because clang gave something the
+	/// parser could not make sense of.
+	#[serde(rename = "skipped-entity")]
+	SkippedEntity,
+	/// A translation unit that clang failed to parse at all. Like clang
+	/// errors, these fail the run unless `--allow-errors` is given.
+	#[serde(rename = "failed-translation-unit")]
+	FailedTranslationUnit,
+	/// Reported by clang while parsing.
+	#[serde(rename = "clang-warning")]
+	ClangWarning,
+	/// Reported by clang while parsing. Declarations after it may be
+	/// missing, so these fail the run unless `--allow-errors` is given.
+	#[serde(rename = "clang-error")]
+	ClangError,
+}
+
+
+
+pub const CATEGORIES: &'static [Category] = &[
+	Category::UnsupportedType,
+	Category::UnsupportedTemplateParam,
+	Category::PrivatePodField,
+	Category::Conflict,
+	Category::SkippedEntity,
+	Category::FailedTranslationUnit,
+	Category::ClangWarning,
+	Category::ClangError,
+];
+
+
+
+#[derive(Clone, PartialEq, Eq, Hash, Debug, Serialize, Deserialize)]
+pub struct Diagnostic {
+	pub category: Category,
+	/// Qualified path of the entity the diagnostic is about, outermost first.
+	pub path: Vec<String>,
+	pub location: Option<SourceLocation>,
+	pub message: String,
+}
+
+
+
+impl Category {
+	pub fn name(&self) -> &'static str {
+		match *self {
+			Category::UnsupportedType => "unsupported-type",
+			Category::UnsupportedTemplateParam => "unsupported-template-param",
+			Category::PrivatePodField => "private-pod-field",
+			Category::Conflict => "conflict",
+			Category::SkippedEntity => "skipped-entity",
+			Category::FailedTranslationUnit => "failed-translation-unit",
+			Category::ClangWarning => "clang-warning",
+			Category::ClangError => "clang-error",
+		}
+	}
+
+
+
+	/// Whether the category means part of a translation unit is missing, which
+	/// fails the run unless `--allow-errors` is given.
+	pub fn is_error(&self) -> bool {
+		match *self {
+			Category::FailedTranslationUnit | Category::ClangError => true,
+			_ => false,
+		}
+	}
+}
+
+
+
+impl FromStr for Category {
+	type Err = String;
+
+	fn from_str(s: &str) -> Result<Category, String> {
+		CATEGORIES.iter().cloned().find(|c| c.name() == s).ok_or_else(|| format!(
+			"unknown diagnostic category `{}`; expected one of {}",
+			s,
+			CATEGORIES.iter().map(|c| c.name()).collect::<Vec<_>>().join(", ")
+		))
+	}
+}
+
+
+
+impl fmt::Display for Category {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		f.write_str(self.name())
+	}
+}
+
+
+
+impl fmt::Display for Diagnostic {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		write!(f, "[{}] ", self.category)?;
+		if !self.path.is_empty() {
+			write!(f, "`{}` ", self.path.join("::"))?;
+		}
+		if let Some(ref location) = self.location {
+			write!(f, "at {} ", location)?;
+		}
+		write!(f, "- {}", self.message)
+	}
+}
+
+
+
+pub fn write_json<W: Write>(writer: &mut W, diagnostics: &[Diagnostic]) -> Result<(), serde_json::Error> {
+	serde_json::to_writer_pretty(writer, &diagnostics)
+}
+
+
+
+/// Writes the number of diagnostics in each category that has any.
+pub fn write_summary<W: Write>(writer: &mut W, diagnostics: &[Diagnostic]) -> io::Result<()> {
+	let width = CATEGORIES.iter().map(|c| c.name().len()).max().unwrap_or(0);
+
+	for category in CATEGORIES.iter() {
+		let count = diagnostics.iter().filter(|d| d.category == *category).count();
+		if count > 0 {
+			writeln!(writer, "{:<width$}  {:>6}", category.name(), count, width = width)?;
+		}
+	}
+	writeln!(writer, "{:<width$}  {:>6}", "total", diagnostics.len(), width = width)
+}
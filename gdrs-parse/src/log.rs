@@ -0,0 +1,70 @@
+//! Leveled logging to stderr. stdout is reserved for the document, so
+//! nothing else may ever be printed there.
+
+use std::fmt;
+use std::io::{self, Write};
+use std::sync::atomic::{AtomicIsize, Ordering, ATOMIC_ISIZE_INIT};
+
+
+
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub enum Level {
+	Error,
+	Warning,
+	Info,
+	Debug,
+}
+
+
+
+// 0 shows warnings and errors; each -v shows one more level, -q hides warnings
+static VERBOSITY: AtomicIsize = ATOMIC_ISIZE_INIT;
+
+
+
+macro_rules! error {
+	($($arg:tt)*) => ($crate::log::log($crate::log::Level::Error, format_args!($($arg)*)))
+}
+
+macro_rules! warning {
+	($($arg:tt)*) => ($crate::log::log($crate::log::Level::Warning, format_args!($($arg)*)))
+}
+
+macro_rules! info {
+	($($arg:tt)*) => ($crate::log::log($crate::log::Level::Info, format_args!($($arg)*)))
+}
+
+macro_rules! debug {
+	($($arg:tt)*) => ($crate::log::log($crate::log::Level::Debug, format_args!($($arg)*)))
+}
+
+
+
+pub fn set_verbosity(verbose: usize, quiet: bool) {
+	VERBOSITY.store(if quiet { -1 } else { verbose as isize }, Ordering::Relaxed);
+}
+
+
+
+pub fn enabled(level: Level) -> bool {
+	let threshold = match level {
+		Level::Error => -1,
+		Level::Warning => 0,
+		Level::Info => 1,
+		Level::Debug => 2,
+	};
+	VERBOSITY.load(Ordering::Relaxed) >= threshold
+}
+
+
+
+pub fn log(level: Level, args: fmt::Arguments) {
+	if enabled(level) {
+		let _ = writeln!(io::stderr(), "{}: {}", match level {
+			Level::Error => "ERROR",
+			Level::Warning => "WARNING",
+			Level::Info => "INFO",
+			Level::Debug => "DEBUG",
+		}, args);
+	}
+}
@@ -1,39 +1,120 @@
 #![feature(proc_macro, custom_derive)]
 
 extern crate serde;
+extern crate serde_json;
 extern crate docopt;
 extern crate clang;
 #[macro_use]
 extern crate serde_derive;
-extern crate serde_json;
 #[macro_use]
 extern crate rustc_serialize;
 extern crate toml;
+extern crate glob;
+extern crate crossbeam;
+extern crate num_cpus;
+extern crate bincode;
 
 extern crate gdrs_api;
 
+#[macro_use]
+mod log;
+mod diagnostics;
+mod cache;
+mod compile_commands;
+mod system_includes;
+
 use std::env;
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 use std::fs;
 use std::path;
-use std::io::{self, Write};
+use std::io::{self, Read};
 use std::ffi::OsStr;
+use std::process;
+use std::panic::{self, AssertUnwindSafe};
+use std::any::Any;
+use std::mem;
+use std::time;
+use std::sync::Mutex;
+use std::sync::atomic::{AtomicUsize, Ordering};
 use docopt::Docopt;
 
 
 
+const DEFAULT_CONFIG: &'static str = "gdrs-parse.toml";
+/// How many rounds of instantiations found while instantiating others are
+/// followed, so a template that uses a bigger instance of itself, such as a
+/// `Foo<T>` holding a `Foo<Foo<T>>`, can't recurse forever.
+const MAX_INSTANTIATION_DEPTH: usize = 8;
+/// Name of the generated translation unit in umbrella mode. It only exists
+/// in memory, and being a `.cpp` nothing declared in it is kept.
+const UMBRELLA: &'static str = "gdrs-umbrella.cpp";
+
+
+
+const EXIT_FAILURE: i32 = 1;
+const EXIT_BREAKING: i32 = 2;
+const EXIT_DENIED: i32 = 3;
+const EXIT_SKIPPED: i32 = 4;
+const EXIT_CLANG_ERRORS: i32 = 5;
+
+
+
 const USAGE: &'static str = r#"
 Parse Godot source and generate JSON API description.
 
 Usage:
-	gdrs-parse [options] <file>...
+	gdrs-parse diff [--breaking] <old> <new>
+	gdrs-parse [options] [-v...] [<file>...]
 	gdrs-parse --help
 
 Options:
-	-o OUTPUT         Output file [default: -]
+	-o OUTPUT         Output file; written in binary if it ends in .bin [default: -]
+	--config CONFIG   Read extra flags and headers from CONFIG [default: gdrs-parse.toml]
+	--godot-version VERSION
+	                  Godot version to record in the output
+	--target TARGET   Build target to record in the output
 	-D DEFINE ...     Define a preprocessor symbol
 	-I INCLUDE ...    Add an #include search path
+	-j JOBS           Parse JOBS translation units at once; defaults to the
+	                  number of CPUs
+	--cache DIR       Keep parsed translation units in DIR and only parse
+	                  them again when they or their headers change
+	--umbrella        Include every input header from a single generated
+	                  translation unit and parse only that; other input
+	                  files are ignored
+	--compile-commands PATH
+	                  Parse each file with the relevant flags it has in the
+	                  compilation database at PATH, and parse every file in
+	                  it if none are given (not used with --umbrella)
+	--detect-system-includes=CXX
+	                  Add the system include directories of the C++
+	                  compiler CXX, a command with any arguments, as
+	                  -isystem flags; without =CXX, $CXX or c++ is used
+	--allow-errors    Write the API description even if clang reports
+	                  errors or a translation unit fails to parse, which
+	                  means parts of it are missing
+	--allow-skipped   Don't fail because single declarations could not be
+	                  parsed; they are still reported
+	--diagnostics FILE
+	                  Write diagnostics to FILE as JSON
+	--deny CATEGORY ...
+	                  Fail if there are diagnostics in CATEGORY
+	--breaking        Only report changes that break existing bindings, and
+	                  exit with an error if there are any
+	-v, --verbose     Log progress to stderr; repeat for more detail
+	-q, --quiet       Only log errors
 	-h, --help        Show this message
+
+Nothing but the API description is ever written to stdout.
+
+Exit status:
+	1  An error stopped gdrs-parse
+	2  diff --breaking found breaking changes
+	3  There were diagnostics in a category passed to --deny
+	4  Some declarations could not be parsed and were left out, and
+	   --allow-skipped wasn't given
+	5  Clang reported errors or a translation unit failed to parse, and
+	   --allow-errors wasn't given; no API description is written
 "#;
 
 
@@ -41,7 +122,11 @@ Options:
 #[derive(Clone, PartialEq, Eq, Debug)]
 enum ParseError {
 	Ignored,
-	Unsupported,
+	/// Carries the reason, for the diagnostic.
+	Unsupported(String),
+	/// Clang returned something the parser did not expect, such as a
+	/// declaration without a name.
+	Malformed(String),
 }
 
 
@@ -50,34 +135,155 @@ enum ParseError {
 #[allow(non_snake_case)]
 struct Args {
 	pub flag_o: String,
+	pub flag_config: String,
+	pub flag_godot_version: Option<String>,
+	pub flag_target: Option<String>,
 	pub flag_D: Option<Vec<String>>,
 	pub flag_I: Option<Vec<String>>,
+	pub flag_j: Option<usize>,
+	pub flag_cache: Option<String>,
+	pub flag_umbrella: bool,
+	pub flag_compile_commands: Option<String>,
+	pub flag_detect_system_includes: Option<String>,
+	pub flag_allow_errors: bool,
+	pub flag_allow_skipped: bool,
+	pub flag_diagnostics: Option<String>,
+	pub flag_deny: Option<Vec<String>>,
+	pub flag_breaking: bool,
+	pub flag_verbose: usize,
+	pub flag_quiet: bool,
 	pub flag_help: bool,
+	pub cmd_diff: bool,
+	pub arg_old: String,
+	pub arg_new: String,
 	pub arg_file: Vec<String>,
 }
 
 
 
-struct TemplateState<'tu> {
-	pub instantiated: HashMap<clang::Entity<'tu>, gdrs_api::Class>,
-	pub pending: HashMap<clang::Entity<'tu>, HashMap<String, gdrs_api::TypeRef>>,
+#[derive(Default, Deserialize)]
+struct Config {
+	#[serde(default)]
+	pub flags: Vec<String>,
+	#[serde(default)]
+	pub headers: Vec<String>,
+	#[serde(default)]
+	pub umbrella: bool,
+}
+
+
+
+struct ParseState<'tu> {
+	pub instantiated: HashMap<clang::Entity<'tu>, Vec<Vec<gdrs_api::TypeRef>>>,
+	pub pending: Vec<(clang::Entity<'tu>, clang::Entity<'tu>, Vec<gdrs_api::TypeRef>)>,
 	pub cur_args: HashMap<String, gdrs_api::TypeRef>,
+	pub cur_layout: Option<clang::Type<'tu>>,
+	/// The declaration being parsed, which diagnostics are reported against
+	/// when there is no more specific entity.
+	pub item: Option<clang::Entity<'tu>>,
+	pub diagnostics: Vec<diagnostics::Diagnostic>,
+}
+
+
+
+impl<'tu> ParseState<'tu> {
+	fn new() -> ParseState<'tu> {
+		ParseState{
+			instantiated: HashMap::with_capacity(0),
+			pending: Vec::with_capacity(0),
+			cur_args: HashMap::with_capacity(0),
+			cur_layout: None,
+			item: None,
+			diagnostics: Vec::new(),
+		}
+	}
+
+
+
+	fn diagnose(&mut self, category: diagnostics::Category, e: Option<clang::Entity<'tu>>, message: String) {
+		let e = e.or(self.item);
+		self.diagnostics.push(diagnostics::Diagnostic{
+			category: category,
+			path: e.map(entity_path).unwrap_or_else(Vec::new),
+			location: e.and_then(|e| parse_location(&e)),
+			message: message,
+		});
+	}
+
+
+
+	/// Reports an entity that was left out because parsing it failed.
+	/// Ignored entities are left out silently.
+	fn skip(&mut self, e: clang::Entity<'tu>, err: ParseError) {
+		match err {
+			ParseError::Ignored => (),
+			ParseError::Unsupported(reason) | ParseError::Malformed(reason) => {
+				let what = e.get_display_name().map(|n| format!("`{}`", n)).unwrap_or_else(|| format!("{:?}", e.get_kind()));
+				self.diagnose(diagnostics::Category::SkippedEntity, Some(e), format!("{} left out: {}", what, reason));
+			},
+		}
+	}
+}
+
+
+
+/// Unwraps clang data that a well-formed entity always has.
+fn require<T>(value: Option<T>, what: &str) -> Result<T, ParseError> {
+	value.ok_or_else(|| ParseError::Malformed(format!("no {}", what)))
 }
 
 
 
 fn main() {
-	let (output, flags, files) = {
-		let Args{flag_o: output, flag_I: includes, flag_D: defines, flag_help: help, arg_file: files} = Docopt::new(USAGE)
-			.and_then(|d| d.argv(env::args().into_iter()).decode())
+	let (output, flags, sources, jobs, cache, allow_errors, allow_skipped, godot_version, target, diagnostics_path, deny) = {
+		let Args{
+			flag_o: output,
+			flag_config: config,
+			flag_godot_version: godot_version,
+			flag_target: target,
+			flag_I: includes,
+			flag_D: defines,
+			flag_j: jobs,
+			flag_cache: cache,
+			flag_umbrella: umbrella,
+			flag_compile_commands: compile_commands,
+			flag_detect_system_includes: detect_system_includes,
+			flag_allow_errors: allow_errors,
+			flag_allow_skipped: allow_skipped,
+			flag_diagnostics: diagnostics_path,
+			flag_deny: deny,
+			flag_breaking: breaking,
+			flag_verbose: verbose,
+			flag_quiet: quiet,
+			flag_help: help,
+			cmd_diff: diff,
+			arg_old: old,
+			arg_new: new,
+			arg_file: mut files,
+		} = Docopt::new(USAGE)
+			.and_then(|d| d.argv(env::args().map(default_cxx)).decode())
 			.unwrap_or_else(|e| e.exit());
 
 		if help {
 			println!("{}", USAGE);
 			return;
 		}
+		log::set_verbosity(verbose, quiet);
+		if diff {
+			return run_diff(&old, &new, breaking);
+		}
 
-		let mut flags = Vec::new();
+		let deny = deny.unwrap_or_else(Vec::new).iter().map(|c| c.parse()).collect::<Result<Vec<diagnostics::Category>, _>>().unwrap_or_else(|e| {
+			error!("{}", e);
+			process::exit(EXIT_FAILURE);
+		});
+
+		let config = load_config(path::Path::new(&config)).unwrap_or_else(|e| {
+			error!("Failed to load config `{}`: {}", config, e);
+			process::exit(EXIT_FAILURE);
+		});
+
+		let mut flags = config.flags;
 		if let Some(defines) = defines {
 			flags.extend(defines.into_iter().map(|d| format!("-D{}", d)));
 		}
@@ -85,235 +291,842 @@ fn main() {
 			flags.extend(includes.into_iter().map(|i| format!("-I{}", i)));
 		}
 
-		(output, flags, files)
+		if let Some(cxx) = detect_system_includes {
+			let dirs = system_includes::detect(&cxx).unwrap_or_else(|e| {
+				error!("Failed to detect system include directories: {}", e);
+				process::exit(EXIT_FAILURE);
+			});
+			for dir in dirs.into_iter() {
+				flags.push("-isystem".to_string());
+				flags.push(dir);
+			}
+		}
+
+		let database = compile_commands.map(|path| compile_commands::Database::load(&path).unwrap_or_else(|e| {
+			error!("Failed to load compilation database `{}`: {}", path, e);
+			process::exit(EXIT_FAILURE);
+		}));
+
+		for pattern in &config.headers {
+			files.extend(expand_glob(pattern).unwrap_or_else(|e| {
+				error!("Bad header pattern `{}`: {}", pattern, e);
+				process::exit(EXIT_FAILURE);
+			}));
+		}
+		if files.is_empty() {
+			files = database.as_ref().map(|d| d.files()).unwrap_or_else(Vec::new);
+		}
+		if files.is_empty() {
+			error!("No input files");
+			process::exit(EXIT_FAILURE);
+		}
+
+		let cache = cache.map(|dir| cache::Cache::open(&dir).unwrap_or_else(|e| {
+			error!("Failed to open cache `{}`: {}", dir, e);
+			process::exit(EXIT_FAILURE);
+		}));
+
+		let sources = if umbrella || config.umbrella {
+			// Source files would be compiled into the one translation unit
+			// together, and clash
+			let headers = files.iter().filter(|f| is_header(path::Path::new(f))).collect::<Vec<_>>();
+			if headers.is_empty() {
+				error!("Umbrella mode needs headers to include, and none of the input files is one");
+				process::exit(EXIT_FAILURE);
+			}
+			if headers.len() < files.len() {
+				warning!("Umbrella mode only includes headers; ignoring {} other input files", files.len() - headers.len());
+			}
+
+			vec![Source{
+				path: UMBRELLA.to_string(),
+				contents: Some(headers.iter().map(|f| format!("#include \"{}\"\n", f)).collect()),
+				flags: vec!["-xc++".to_string()],
+			}]
+		} else {
+			// Files are parsed as C++, headers included, unless their compile
+			// command says otherwise
+			files.into_iter().map(|f| {
+				let flags = match database.as_ref() {
+					Some(database) => match database.flags(&f) {
+						Some(flags) => flags.to_vec(),
+						None => {
+							warning!("No compile command for `{}`; parsing it with the common flags only", f);
+							vec!["-xc++".to_string()]
+						},
+					},
+					None => vec!["-xc++".to_string()],
+				};
+
+				Source{path: f, contents: None, flags: flags}
+			}).collect()
+		};
+
+		(output, flags, sources, jobs, cache, allow_errors, allow_skipped, godot_version, target, diagnostics_path, deny)
 	};
 
-	let c = clang::Clang::new().unwrap();
-	let mut index = clang::Index::new(&c, true, true);
-	index.set_thread_options(clang::ThreadOptions{editing: false, indexing: false});
+	let c = clang::Clang::new().unwrap_or_else(|e| {
+		error!("Failed to load libclang: {}", e);
+		process::exit(EXIT_FAILURE);
+	});
 
-	let mut api = gdrs_api::Namespace{
-		name: "".to_string(),
-		globals: Vec::with_capacity(0),
-		enums: Vec::with_capacity(0),
-		aliases: Vec::with_capacity(0),
-		functions: Vec::with_capacity(0),
-		classes: Vec::with_capacity(0),
-		namespaces: Vec::with_capacity(0),
+	// Each worker has an index of its own and claims the next unparsed file
+	let next = AtomicUsize::new(0);
+	let units = Mutex::new(sources.iter().map(|_| None).collect::<Vec<_>>());
+	crossbeam::scope(|scope| {
+		for _ in 0..jobs.unwrap_or_else(num_cpus::get).max(1).min(sources.len()) {
+			scope.spawn(|| {
+				// Clang's own diagnostic output is off; they are reported
+				// through clang_diagnostic instead
+				let mut index = clang::Index::new(&c, true, false);
+				index.set_thread_options(clang::ThreadOptions{editing: false, indexing: false});
+
+				loop {
+					let i = next.fetch_add(1, Ordering::SeqCst);
+					if i >= sources.len() {
+						break;
+					}
+
+					// A panic while parsing one file only loses that file
+					let unit = panic::catch_unwind(AssertUnwindSafe(|| {
+						parse_unit(&index, &sources[i], &flags, cache.as_ref())
+					})).unwrap_or_else(|e| failed_unit(&sources[i].path, &format!("the parser panicked: {}", panic_message(&e))));
+					units.lock().unwrap()[i] = Some(unit);
+				}
+			});
+		}
+	});
+
+	let mut api = gdrs_api::Namespace::new("".to_string());
+	// Headers are seen by many translation units; report each problem once
+	let mut reported = Vec::new();
+	let mut seen = HashSet::new();
+	let mut failed = 0;
+
+	// Merged in the order the files were given, so the output doesn't depend
+	// on which worker finished first
+	for (unit, source) in units.into_inner().unwrap().into_iter().zip(sources.iter()) {
+		let Unit{ns, mut diagnostics} = unit.unwrap_or_else(|| failed_unit(&source.path, "it was never parsed"));
+		if diagnostics.iter().any(|d| d.category.is_error()) {
+			failed += 1;
+		}
+		if let Some(ns) = ns {
+			for conflict in api.merge(ns) {
+				diagnostics.push(conflict_diagnostic(Vec::new(), conflict));
+			}
+		}
+
+		for d in diagnostics.into_iter() {
+			if seen.insert(d.clone()) {
+				if d.category.is_error() {
+					error!("{}", d);
+				} else {
+					warning!("{}", d);
+				}
+				reported.push(d);
+			}
+		}
+	}
+
+	let doc = gdrs_api::ApiDocument{
+		schema_version: gdrs_api::SCHEMA_VERSION,
+		godot_version: godot_version,
+		target: target,
+		defines: flags.iter().filter(|f| f.starts_with("-D")).map(|f| f[2..].to_string()).collect(),
+		generator_version: env!("CARGO_PKG_VERSION").to_string(),
+		generated_at: time::SystemTime::now().duration_since(time::UNIX_EPOCH).map(|d| d.as_secs()).unwrap_or(0),
+		root: api,
 	};
 
-	for file in &files {
-		let mut parser = index.parser(file);
-		parser.arguments(&flags);
-		//let parser = parser.detailed_preprocessing_record(true);
-		let parser = parser.skip_function_bodies(true);
-		let tu = parser.parse().unwrap();
-		let mut ts = TemplateState{
-			instantiated: HashMap::with_capacity(0),
-			pending: HashMap::with_capacity(0),
-			cur_args: HashMap::with_capacity(0),
-		};
-		api.merge(parse_namespace(tu.get_entity(), &mut ts).unwrap());
+	// A truncated API is worse than none; keep the previous output instead
+	if failed == 0 || allow_errors {
+		if output == "-" {
+			let stdout = io::stdout();
+			gdrs_api::io::write_json(&mut stdout.lock(), &doc).unwrap_or_else(|e| {
+				error!("Failed to write output: {}", e);
+				process::exit(EXIT_FAILURE);
+			});
+		} else {
+			let format = gdrs_api::io::Format::from_path(&output);
+			fs::File::create(path::Path::new(&output))
+				.map_err(gdrs_api::io::Error::from)
+				.and_then(|file| gdrs_api::io::write(&mut io::BufWriter::new(file), &doc, format))
+				.unwrap_or_else(|e| {
+					error!("Failed to write `{}`: {}", output, e);
+					process::exit(EXIT_FAILURE);
+				});
+		}
+	}
+
+	if !reported.is_empty() && log::enabled(log::Level::Warning) {
+		let _ = diagnostics::write_summary(&mut io::stderr(), &reported);
+	}
+	if let Some(diagnostics_path) = diagnostics_path {
+		fs::File::create(path::Path::new(&diagnostics_path))
+			.map_err(|e| e.to_string())
+			.and_then(|file| diagnostics::write_json(&mut io::BufWriter::new(file), &reported).map_err(|e| e.to_string()))
+			.unwrap_or_else(|e| {
+				error!("Failed to write `{}`: {}", diagnostics_path, e);
+				process::exit(EXIT_FAILURE);
+			});
+	}
+
+	if failed > 0 && !allow_errors {
+		error!("{} translation units failed to parse or had errors; pass --allow-errors to write the API description anyway", failed);
+		process::exit(EXIT_CLANG_ERRORS);
+	}
+	let denied = reported.iter().filter(|d| deny.contains(&d.category)).count();
+	if denied > 0 {
+		error!("{} diagnostics in denied categories", denied);
+		process::exit(EXIT_DENIED);
+	}
+	let skipped = reported.iter().filter(|d| d.category == diagnostics::Category::SkippedEntity).count();
+	if skipped > 0 && !allow_skipped {
+		error!("{} declarations left out; pass --allow-skipped to accept that", skipped);
+		process::exit(EXIT_SKIPPED);
+	}
+}
+
+
+
+/// A translation unit to parse. Generated ones carry their contents, and
+/// `path` doesn't exist on disk.
+struct Source {
+	path: String,
+	contents: Option<String>,
+	/// Flags for this file alone, from the compilation database.
+	flags: Vec<String>,
+}
+
+
+
+/// What a single translation unit contributes to the API.
+#[derive(Clone, Serialize, Deserialize)]
+struct Unit {
+	ns: Option<gdrs_api::Namespace>,
+	diagnostics: Vec<diagnostics::Diagnostic>,
+}
+
+
 
-		println!("PENDING: {:?}", ts.pending);
+fn parse_unit(index: &clang::Index, source: &Source, flags: &[String], cache: Option<&cache::Cache>) -> Unit {
+	let file = &source.path[..];
+	let contents = source.contents.as_ref().map(|c| &c[..]);
+	let mut flags = flags.to_vec();
+	flags.extend(source.flags.iter().cloned());
+	let flags = &flags[..];
+	if let Some(unit) = cache.and_then(|c| c.load(file, contents, flags)) {
+		debug!("Reusing cached `{}`", file);
+		return unit;
 	}
 
-	let json = serde_json::to_string_pretty(&api).unwrap();
-	if output == "-" {
-		println!("{}", json);
+	info!("Parsing `{}`", file);
+	let mut parser = index.parser(file);
+	parser.arguments(flags);
+	if let Some(contents) = contents {
+		parser.unsaved(&[clang::Unsaved::new(file, contents)]);
+	}
+	// Inclusion directives are only in the detailed record, and the cache
+	// needs them to know which headers an entry depends on
+	let parser = parser.skip_function_bodies(true).detailed_preprocessing_record(cache.is_some());
+
+	match parser.parse() {
+		Ok(tu) => {
+			let unit = {
+				let mut ps = ParseState::new();
+				ps.diagnostics.extend(tu.get_diagnostics().into_iter().filter_map(clang_diagnostic));
+				let ns = parse_namespace(tu.get_entity(), &mut ps).map(|mut ns| {
+					instantiate_templates(&mut ns, &mut ps);
+					ns
+				});
+
+				Unit{ns: ns, diagnostics: ps.diagnostics}
+			};
+
+			if let Some(cache) = cache {
+				let mut inputs = if contents.is_none() { vec![path::PathBuf::from(file)] } else { Vec::new() };
+				let mut unresolved = Vec::new();
+				for e in tu.get_entity().get_children().into_iter().filter(|e| e.get_kind() == clang::EntityKind::InclusionDirective) {
+					match e.get_file() {
+						Some(f) => inputs.push(f.get_path()),
+						None => unresolved.push(e.get_name().unwrap_or_else(String::new)),
+					}
+				}
+				if let Err(e) = cache.store(file, contents, flags, inputs, unresolved, &unit) {
+					warning!("Failed to cache `{}`: {}", file, e);
+				}
+			}
+
+			unit
+		},
+		Err(e) => failed_unit(file, &e.to_string()),
+	}
+}
+
+
+
+/// A translation unit that is left out entirely, with the reason why.
+fn failed_unit(file: &str, reason: &str) -> Unit {
+	Unit{
+		ns: None,
+		diagnostics: vec![diagnostics::Diagnostic{
+			category: diagnostics::Category::FailedTranslationUnit,
+			path: Vec::new(),
+			location: None,
+			message: format!("`{}` left out: {}", file, reason),
+		}],
+	}
+}
+
+
+
+/// The message a panic was started with, if it has one.
+fn panic_message(e: &Box<Any + Send>) -> String {
+	if let Some(s) = e.downcast_ref::<&'static str>() {
+		s.to_string()
+	} else if let Some(s) = e.downcast_ref::<String>() {
+		s.clone()
 	} else {
-		let mut file = fs::File::create(path::Path::new(&output)).unwrap();
-		write!(file, "{}", json).unwrap();
+		"no message".to_string()
 	}
 }
 
 
 
-fn parse_namespace<'tu>(e: clang::Entity<'tu>, ts: &mut TemplateState<'tu>) -> Option<gdrs_api::Namespace> {
-	let name = e.get_name();
-	if name.is_none() {
-		return None;
+/// Converts an error or warning reported by clang itself. Notes are left out.
+fn clang_diagnostic(d: clang::diagnostic::Diagnostic) -> Option<diagnostics::Diagnostic> {
+	let category = match d.get_severity() {
+		clang::diagnostic::Severity::Error | clang::diagnostic::Severity::Fatal => diagnostics::Category::ClangError,
+		clang::diagnostic::Severity::Warning => diagnostics::Category::ClangWarning,
+		clang::diagnostic::Severity::Ignored | clang::diagnostic::Severity::Note => return None,
+	};
+
+	Some(diagnostics::Diagnostic{
+		category: category,
+		path: Vec::new(),
+		location: Some(convert_location(d.get_location())),
+		message: d.get_text(),
+	})
+}
+
+
+
+/// Compares two API descriptions and prints the changes, exiting with an
+/// error if `breaking` is set and any of them break existing bindings.
+fn run_diff(old: &str, new: &str, breaking: bool) {
+	let load = |path: &str| fs::File::open(path)
+		.map_err(gdrs_api::io::Error::from)
+		.and_then(|file| gdrs_api::io::read(io::BufReader::new(file), gdrs_api::io::Format::from_path(path)))
+		.unwrap_or_else(|e| {
+			error!("Failed to read `{}`: {}", path, e);
+			process::exit(EXIT_FAILURE);
+		});
+	let old = load(old);
+	let new = load(new);
+
+	let changes = gdrs_api::diff::diff(&old.root, &new.root);
+	let mut broken = false;
+	for change in changes.iter().filter(|c| c.breaking || !breaking) {
+		println!("{} {}", if change.breaking { "!" } else { " " }, change);
+		broken |= change.breaking;
 	}
 
-	let mut ns = gdrs_api::Namespace{
-		name: name.unwrap(),
-		globals: Vec::with_capacity(0),
-		enums: Vec::with_capacity(0),
-		aliases: Vec::with_capacity(0),
-		functions: Vec::with_capacity(0),
-		classes: Vec::with_capacity(0),
-		namespaces: Vec::with_capacity(0),
+	if breaking && broken {
+		process::exit(EXIT_BREAKING);
+	}
+}
+
+
+
+/// Loads the config file at `path`. A missing file is only an error if
+/// it was asked for explicitly; the default config is optional.
+fn load_config(path: &path::Path) -> Result<Config, String> {
+	let mut source = String::new();
+	match fs::File::open(path) {
+		Ok(mut file) => { file.read_to_string(&mut source).map_err(|e| e.to_string())?; },
+		Err(ref e) if e.kind() == io::ErrorKind::NotFound && path == path::Path::new(DEFAULT_CONFIG) => return Ok(Config::default()),
+		Err(e) => return Err(e.to_string()),
+	}
+
+	let mut parser = toml::Parser::new(&source);
+	let table = match parser.parse() {
+		Some(table) => table,
+		None => return Err(parser.errors.iter().map(|e| {
+			let (line, col) = parser.to_linecol(e.lo);
+			format!("{}:{}: {}", line + 1, col + 1, e)
+		}).collect::<Vec<_>>().join("; ")),
 	};
 
-	e.visit_children(|c, _| {
-		if c.is_in_system_header() {
-			return clang::EntityVisitResult::Continue;
+	serde::Deserialize::deserialize(&mut toml::Decoder::new(toml::Value::Table(table))).map_err(|e: toml::DecodeError| e.to_string())
+}
+
+
+
+/// Docopt has no options with an optional value, so a bare
+/// `--detect-system-includes` is given the default compiler before parsing.
+fn default_cxx(arg: String) -> String {
+	if arg == "--detect-system-includes" {
+		format!("{}={}", arg, env::var("CXX").unwrap_or_else(|_| "c++".to_string()))
+	} else {
+		arg
+	}
+}
+
+
+
+/// Expands a header glob into the sorted list of matching files. Patterns
+/// are relative to the working directory, like `-I` paths in the flags.
+fn expand_glob(pattern: &str) -> Result<Vec<String>, String> {
+	let mut files = Vec::new();
+	for entry in glob::glob(pattern).map_err(|e| e.to_string())? {
+		let entry = entry.map_err(|e| e.to_string())?;
+		if entry.is_file() {
+			files.push(entry.to_string_lossy().into_owned());
 		}
-		let loc = c.get_location().unwrap().get_expansion_location().file.get_path();
-		if loc.extension() == Some(OsStr::new("cpp")) || loc.components().any(|c| c == path::Component::Normal(OsStr::new("thirdparty"))) {
-			return clang::EntityVisitResult::Continue;
+	}
+	files.sort();
+
+	Ok(files)
+}
+
+
+
+fn parse_namespace<'tu>(e: clang::Entity<'tu>, ps: &mut ParseState<'tu>) -> Option<gdrs_api::Namespace> {
+	let mut ns = match e.get_name() {
+		Some(name) => gdrs_api::Namespace::new(name),
+		None => return None,
+	};
+
+	e.visit_children(|c, _| {
+		if let Err(err) = parse_namespace_member(c, &mut ns, ps) {
+			ps.skip(c, err);
 		}
-		let loc = loc.to_str().unwrap();
-
-		match c.get_kind() {
-			clang::EntityKind::VarDecl => {
-				if c.get_type().unwrap().is_const_qualified() {
-					if let Some(val) = c.get_child(0).and_then(|exp| parse_value(exp)) {
-						let mut ty = parse_type(c.get_type().unwrap(), ts).or_else(|_| parse_type(c.get_child(0).unwrap().get_type().unwrap(), ts)).unwrap();
-						ty.value = Some(val);
-						ns.globals.push(gdrs_api::Var{
-							ty: ty,
-							name: c.get_name().unwrap(),
-						})
-					}
-				} else if c.get_storage_class() == Some(clang::StorageClass::Extern) {
-					match parse_type(c.get_type().unwrap(), ts) {
-						Ok(ty) => ns.globals.push(gdrs_api::Var{
-							ty: ty,
-							name: c.get_name().unwrap(),
-						}),
-						Err(ParseError::Unsupported) => {
-							let _ = writeln!(io::stderr(), "WARNING: Unsupported extern global type `{}`: {:?}", c.get_name().unwrap(), c);
+
+		clang::EntityVisitResult::Continue
+	});
+
+	Some(ns)
+}
+
+
+
+fn parse_namespace_member<'tu>(c: clang::Entity<'tu>, ns: &mut gdrs_api::Namespace, ps: &mut ParseState<'tu>) -> Result<(), ParseError> {
+	if c.is_in_system_header() {
+		return Ok(());
+	}
+	let loc = require(c.get_location(), "location")?.get_expansion_location().file.get_path();
+	if is_excluded_path(&loc) {
+		return Ok(());
+	}
+	let loc = loc.to_string_lossy().into_owned();
+	ps.item = Some(c);
+
+	match c.get_kind() {
+		clang::EntityKind::VarDecl => {
+			let ty = require(c.get_type(), "type")?;
+			let name = require(c.get_name(), "name")?;
+
+			if ty.is_const_qualified() {
+				let init = value_children(c).pop();
+				if let Some(val) = init.and_then(|exp| parse_value(exp, ps)) {
+					let mut ty = parse_type(ty, ps).or_else(|_| parse_type(require(init.and_then(|i| i.get_type()), "initializer type")?, ps))?;
+					ty.value = Some(val);
+					ns.globals.push(gdrs_api::Var{
+						ty: ty,
+						name: name,
+						location: parse_location(&c),
+						doc: parse_doc(&c),
+					})
+				}
+			} else if c.get_storage_class() == Some(clang::StorageClass::Static) {
+				// Internal linkage; every translation unit gets its own copy
+				debug!("Skipping static global `{}`", name);
+			} else {
+				ns.globals.push(gdrs_api::Var{
+					ty: parse_type(ty, ps)?,
+					name: name,
+					location: parse_location(&c),
+					doc: parse_doc(&c),
+				});
+			}
+		},
+		clang::EntityKind::EnumDecl => {
+			let _enum = parse_enum(&c, ps)?;
+			if _enum.name == "auto" {
+				let gdrs_api::Enum{variants, underlying, ..} = _enum;
+				for v in variants.into_iter() {
+					ns.globals.push(gdrs_api::Var{
+						name: v.name,
+						ty: gdrs_api::TypeRef{kind: underlying.clone(), is_const: true, value: Some(v.value)},
+						location: v.location,
+						doc: v.doc,
+					});
+				}
+			} else {
+				ns.enums.push(_enum);
+			}
+		},
+		clang::EntityKind::TypeAliasDecl | clang::EntityKind::TypedefDecl => {
+			if let Some(underlying) = require(c.get_typedef_underlying_type(), "underlying type")?.get_declaration() {
+				if underlying.get_name().is_none() {
+					match underlying.get_kind() {
+						clang::EntityKind::EnumDecl => {
+							let mut _enum = parse_enum(&underlying, ps)?;
+							_enum.name = require(c.get_name(), "name")?;
+							ns.enums.push(_enum);
+						},
+						clang::EntityKind::ClassDecl | clang::EntityKind::StructDecl | clang::EntityKind::UnionDecl => {
+							if let Some(mut class) = parse_class(underlying, loc, ps) {
+								class.name.name = require(c.get_name(), "name")?;
+								ns.classes.push(class);
+							}
 						},
 						_ => (),
 					}
 				} else {
-					println!("{:#?}", c.get_type().unwrap().get_declaration().unwrap().get_template().unwrap().get_child(3).unwrap().get_children());
+					ns.aliases.push(parse_alias(c, ps)?);
 				}
-			},
-			clang::EntityKind::EnumDecl => {
-				let _enum = parse_enum(&c, ts);
-				if _enum.name == "auto" {
-					let gdrs_api::Enum{variants, underlying, ..} = _enum;
-					for v in variants.into_iter() {
-						ns.globals.push(gdrs_api::Var{
-							name: v.name,
-							ty: gdrs_api::TypeRef{kind: underlying.clone(), semantic: gdrs_api::TypeSemantic::Value, is_const: true, value: Some(v.value)},
-						});
-					}
-				} else {
-					ns.enums.push(_enum);
-				}
-			},
-			clang::EntityKind::TypeAliasDecl | clang::EntityKind::TypedefDecl => {
-				if let Some(underlying) = c.get_typedef_underlying_type().unwrap().get_declaration() {
-					if underlying.get_name().is_none() {
-						match underlying.get_kind() {
-							clang::EntityKind::EnumDecl => {
-								let mut _enum = parse_enum(&underlying, ts);
-								_enum.name = c.get_name().unwrap();
-								ns.enums.push(_enum);
-							},
-							clang::EntityKind::ClassDecl | clang::EntityKind::StructDecl | clang::EntityKind::UnionDecl => {
-								if let Some(mut class) = parse_class(underlying, loc.to_string(), ts) {
-									class.name.name = c.get_name().unwrap();
-									ns.classes.push(class);
-								}
-							},
-							_ => (),
-						}
-					} else if let Some(alias) = parse_alias(c, ts) {
-						ns.aliases.push(alias);
+			}
+		},
+		clang::EntityKind::ClassDecl | clang::EntityKind::StructDecl => {
+			if c.get_template().is_none() {
+				if let Some(class) = parse_class(c, loc, ps) {
+					if class.name.name != "auto" {
+						ns.classes.push(class);
 					}
 				}
-			},
-			clang::EntityKind::ClassDecl | clang::EntityKind::StructDecl => {
-				if c.get_template().is_none() {
-					if let Some(class) = parse_class(c, loc.to_string(), ts) {
-						if class.name.name != "auto" {
-							ns.classes.push(class);
-						}
-					}
+			}
+		},
+		clang::EntityKind::ClassTemplate => {
+			if let Some(template) = parse_class_template(c, loc, ps) {
+				ns.templates.push(template);
+			}
+		},
+		clang::EntityKind::UnionDecl => {
+			if let Some(union) = parse_class(c, loc, ps) {
+				if union.name.name != "auto" {
+					ns.classes.push(union);
 				}
-			},
-			clang::EntityKind::UnionDecl => {
-				if let Some(union) = parse_class(c, loc.to_string(), ts) {
-					if union.name.name != "auto" {
-						ns.classes.push(union);
+			}
+		},
+		clang::EntityKind::FunctionDecl => {
+			ns.functions.push(parse_function(c, ps)?);
+		},
+		clang::EntityKind::Namespace => {
+			if let Some(cns) = parse_namespace(c, ps) {
+				if let Some(dns) = ns.namespaces.iter_mut().find(|dns| dns.name == cns.name) {
+					for conflict in dns.merge(cns) {
+						ps.diagnostics.push(conflict_diagnostic(entity_path(c), conflict));
 					}
+					return Ok(());
 				}
+
+				ns.namespaces.push(cns);
+			}
+		},
+		_ => (),
+	}
+
+	Ok(())
+}
+
+
+
+fn is_header(path: &path::Path) -> bool {
+	match path.extension().and_then(|e| e.to_str()) {
+		Some("h") | Some("hh") | Some("hpp") | Some("hxx") | Some("inc") => true,
+		_ => false,
+	}
+}
+
+
+
+/// Whether declarations in `path` are left out of the API: anything local to
+/// a `.cpp` file, and bundled third party libraries.
+fn is_excluded_path(path: &path::Path) -> bool {
+	path.extension() == Some(OsStr::new("cpp")) || path.components().any(|c| c == path::Component::Normal(OsStr::new("thirdparty")))
+}
+
+
+
+fn parse_class_template<'tu>(e: clang::Entity<'tu>, loc: String, ps: &mut ParseState<'tu>) -> Option<gdrs_api::ClassTemplate> {
+	let mut params = Vec::new();
+	for c in e.get_children().into_iter() {
+		let kind = match c.get_kind() {
+			clang::EntityKind::TemplateTypeParameter => gdrs_api::TemplateParamKind::Type,
+			clang::EntityKind::NonTypeTemplateParameter => match require(c.get_type(), "type").and_then(|t| parse_type(t, ps)) {
+				Ok(ty) => gdrs_api::TemplateParamKind::Value(ty),
+				Err(_) => {
+					ps.diagnose(
+						diagnostics::Category::UnsupportedTemplateParam,
+						Some(e),
+						format!("template parameter `{}` has an unsupported type", c.get_name().unwrap_or_else(String::new)),
+					);
+					return None;
+				},
 			},
-			clang::EntityKind::FunctionDecl => {
-				if let Some(func) = parse_function(c, ts) {
-					ns.functions.push(func);
+			clang::EntityKind::TemplateTemplateParameter => gdrs_api::TemplateParamKind::Template,
+			_ => continue,
+		};
+
+		params.push(gdrs_api::TemplateParam{name: c.get_name().unwrap_or_else(String::new), kind: kind});
+	}
+
+	// The pattern is parsed with no bindings so members refer to the
+	// template's own parameters, even when reached during an instantiation.
+	let outer = mem::replace(&mut ps.cur_args, HashMap::with_capacity(0));
+	let class = parse_class(e, loc, ps);
+	ps.cur_args = outer;
+
+	class.map(|class| gdrs_api::ClassTemplate{params: params, class: class})
+}
+
+
+
+/// Records a use of a template specialization so it gets instantiated once
+/// the translation unit has been walked.
+fn queue_instantiation<'tu>(spec: clang::Entity<'tu>, template: clang::Entity<'tu>, args: &[gdrs_api::TypeRef], ps: &mut ParseState<'tu>) {
+	if args.iter().any(|a| a.is_dependent()) || template.is_in_system_header() || template.get_kind() == clang::EntityKind::ClassTemplatePartialSpecialization {
+		return;
+	}
+	if template.get_location().map(|l| is_excluded_path(&l.get_expansion_location().file.get_path())).unwrap_or(true) {
+		return;
+	}
+
+	// Implicit instantiations share the location of their template; anything
+	// else is an explicit specialization with a definition of its own.
+	let source = if spec.get_location() == template.get_location() { template } else { spec };
+
+	let queued = ps.instantiated.entry(source).or_insert_with(Vec::new);
+	if !queued.iter().any(|a| &a[..] == args) {
+		queued.push(args.to_vec());
+		ps.pending.push((source, spec, args.to_vec()));
+	}
+}
+
+
+
+fn instantiate_templates<'tu>(ns: &mut gdrs_api::Namespace, ps: &mut ParseState<'tu>) {
+	let mut depth = 0;
+	while !ps.pending.is_empty() {
+		if depth == MAX_INSTANTIATION_DEPTH {
+			for (_, spec, _) in mem::replace(&mut ps.pending, Vec::with_capacity(0)).into_iter() {
+				ps.skip(spec, ParseError::Unsupported(format!("instantiations nested more than {} deep", MAX_INSTANTIATION_DEPTH)));
+			}
+			break;
+		}
+		depth += 1;
+
+		for (e, spec, args) in mem::replace(&mut ps.pending, Vec::with_capacity(0)).into_iter() {
+			let path = match namespace_path(e) {
+				Some(path) => path,
+				None => continue,
+			};
+
+			if let Some(class) = instantiate_template(e, spec, args, ps) {
+				let dest = namespace_at(ns, &path);
+				if !dest.classes.iter().any(|c| c.name == class.name) {
+					dest.classes.push(class);
 				}
+			}
+		}
+	}
+}
+
+
+
+fn instantiate_template<'tu>(e: clang::Entity<'tu>, spec: clang::Entity<'tu>, args: Vec<gdrs_api::TypeRef>, ps: &mut ParseState<'tu>) -> Option<gdrs_api::Class> {
+	let mut bindings = HashMap::with_capacity(args.len());
+	if e.get_kind() == clang::EntityKind::ClassTemplate {
+		let params = e.get_children().into_iter().filter(|c| match c.get_kind() {
+			clang::EntityKind::TemplateTypeParameter
+			| clang::EntityKind::NonTypeTemplateParameter
+			| clang::EntityKind::TemplateTemplateParameter
+			=> true,
+			_ => false,
+		}).collect::<Vec<_>>();
+
+		if params.len() != args.len() || params.iter().any(|p| p.get_kind() != clang::EntityKind::TemplateTypeParameter) {
+			ps.skip(spec, ParseError::Unsupported("only templates with type parameters alone are instantiated".to_string()));
+			return None;
+		}
+
+		for (p, a) in params.into_iter().zip(args.iter()) {
+			bindings.insert(p.get_name().unwrap_or_else(String::new), a.clone());
+		}
+	}
+
+	let include = match e.get_location() {
+		Some(location) => location.get_expansion_location().file.get_path().to_string_lossy().into_owned(),
+		None => {
+			ps.skip(spec, ParseError::Malformed("no location".to_string()));
+			return None;
+		},
+	};
+	// Layout queries only work on the specialization, not on the pattern.
+	let outer = mem::replace(&mut ps.cur_args, bindings);
+	ps.cur_layout = spec.get_type();
+	let class = parse_class(e, include, ps);
+	ps.cur_args = outer;
+
+	class.map(|mut class| {
+		class.name.args = args;
+		class
+	})
+}
+
+
+
+/// Names of the namespaces enclosing `e`, outermost first. `None` if `e` is
+/// nested in a class or an anonymous namespace.
+fn namespace_path(e: clang::Entity) -> Option<Vec<String>> {
+	let mut path = Vec::new();
+	let mut p = e.get_semantic_parent();
+	while let Some(parent) = p {
+		match parent.get_kind() {
+			clang::EntityKind::TranslationUnit => break,
+			clang::EntityKind::Namespace => match parent.get_name() {
+				Some(name) => path.push(name),
+				None => return None,
 			},
-			clang::EntityKind::Namespace => {
-				if let Some(cns) = parse_namespace(c, ts) {
-					if let Some(dns) = ns.namespaces.iter_mut().find(|dns| dns.name == cns.name) {
-						dns.merge(cns);
-						return clang::EntityVisitResult::Continue;
-					}
+			clang::EntityKind::UnexposedDecl if parent.get_name().is_none() => (),
+			_ => return None,
+		}
+		p = parent.get_semantic_parent();
+	}
+	path.reverse();
+
+	Some(path)
+}
+
+
+
+/// Qualified name of `e`, outermost first. Anonymous scopes are left out.
+fn entity_path(e: clang::Entity) -> Vec<String> {
+	let mut path = Vec::new();
+	let mut p = Some(e);
+	while let Some(entity) = p {
+		if entity.get_kind() == clang::EntityKind::TranslationUnit {
+			break;
+		}
+		if let Some(name) = entity.get_name() {
+			path.push(name);
+		}
+		p = entity.get_semantic_parent();
+	}
+	path.reverse();
+
+	path
+}
+
+
+
+/// Reports a merge conflict from a namespace at `path`.
+fn conflict_diagnostic(mut path: Vec<String>, conflict: gdrs_api::Conflict) -> diagnostics::Diagnostic {
+	path.extend(conflict.path.into_iter());
 
-					ns.namespaces.push(cns);
-				}
-			},
-			_ => (),
-		}
+	diagnostics::Diagnostic{
+		category: diagnostics::Category::Conflict,
+		path: path,
+		location: conflict.incoming,
+		message: match conflict.existing {
+			Some(existing) => format!("conflicting {} definition; keeping the one at {}", conflict.kind, existing),
+			None => format!("conflicting {} definition", conflict.kind),
+		},
+	}
+}
 
-		clang::EntityVisitResult::Continue
-	});
 
-	Some(ns)
+
+fn namespace_at<'a>(ns: &'a mut gdrs_api::Namespace, path: &[String]) -> &'a mut gdrs_api::Namespace {
+	if path.is_empty() {
+		return ns;
+	}
+
+	let i = match ns.namespaces.iter().position(|n| n.name == path[0]) {
+		Some(i) => i,
+		None => {
+			ns.namespaces.push(gdrs_api::Namespace::new(path[0].clone()));
+			ns.namespaces.len() - 1
+		},
+	};
+
+	namespace_at(&mut ns.namespaces[i], &path[1..])
 }
 
 
 
-fn parse_enum<'tu>(e: &clang::Entity, ts: &mut TemplateState<'tu>) -> gdrs_api::Enum {
-	let underlying = parse_type(e.get_enum_underlying_type().unwrap(), ts).unwrap().kind;
+fn parse_enum<'tu>(e: &clang::Entity<'tu>, ps: &mut ParseState<'tu>) -> Result<gdrs_api::Enum, ParseError> {
+	let underlying = parse_type(require(e.get_enum_underlying_type(), "underlying type")?, ps)?.kind;
 	let mut _enum = gdrs_api::Enum{
 		name: e.get_name().unwrap_or_else(|| "auto".to_string()),
 		underlying: underlying,
 		variants: Vec::new(),
+		location: parse_location(e),
+		doc: parse_doc(e),
 	};
 
-	e.visit_children(|c, _| {
-		_enum.variants.push(gdrs_api::Variant{
-			name: c.get_name().unwrap(),
-			value: match _enum.underlying {
-				gdrs_api::TypeKind::Char | gdrs_api::TypeKind::Short | gdrs_api::TypeKind::Int | gdrs_api::TypeKind::Long | gdrs_api::TypeKind::LongLong
-					=> gdrs_api::Value::Int(c.get_enum_constant_value().map(|(v, _)| v).unwrap()),
-				gdrs_api::TypeKind::UChar | gdrs_api::TypeKind::UShort | gdrs_api::TypeKind::UInt | gdrs_api::TypeKind::ULong | gdrs_api::TypeKind::ULongLong
-					=> gdrs_api::Value::UInt(c.get_enum_constant_value().map(|(_, v)| v).unwrap()),
-				_ => unreachable!(),
-			},
-		});
-
-		clang::EntityVisitResult::Continue
-	});
+	for c in e.get_children().into_iter().filter(|c| c.get_kind() == clang::EntityKind::EnumConstantDecl) {
+		match parse_variant(c, &_enum.underlying) {
+			Ok(variant) => _enum.variants.push(variant),
+			Err(err) => ps.skip(c, err),
+		}
+	}
 
-	_enum
+	Ok(_enum)
 }
 
 
 
-fn parse_alias<'tu>(e: clang::Entity<'tu>, ts: &mut TemplateState<'tu>) -> Option<gdrs_api::TypeAlias> {
-	match parse_type(e.get_typedef_underlying_type().unwrap(), ts) {
-		Ok(ty) => Some(gdrs_api::TypeAlias{
-			name: gdrs_api::ScopeName{name: e.get_name().unwrap(), args: Vec::with_capacity(0)},
-			ty: ty,
-		}),
-		Err(ParseError::Unsupported) => {
-			let _ = writeln!(io::stderr(), "WARNING: Unsupported alias type `{}`: {:?}", e.get_name().unwrap(), e);
-			None
+fn parse_variant(c: clang::Entity, underlying: &gdrs_api::TypeKind) -> Result<gdrs_api::Variant, ParseError> {
+	let (signed, unsigned) = require(c.get_enum_constant_value(), "value")?;
+
+	Ok(gdrs_api::Variant{
+		name: require(c.get_name(), "name")?,
+		value: match *underlying {
+			gdrs_api::TypeKind::Char | gdrs_api::TypeKind::Short | gdrs_api::TypeKind::Int | gdrs_api::TypeKind::Long | gdrs_api::TypeKind::LongLong
+				=> gdrs_api::Value::Int(signed),
+			gdrs_api::TypeKind::UChar | gdrs_api::TypeKind::UShort | gdrs_api::TypeKind::UInt | gdrs_api::TypeKind::ULong | gdrs_api::TypeKind::ULongLong
+				=> gdrs_api::Value::UInt(unsigned),
+			gdrs_api::TypeKind::Bool => gdrs_api::Value::Bool(unsigned != 0),
+			// wchar_t, or a character type recorded as opaque
+			_ => if signed < 0 { gdrs_api::Value::Int(signed) } else { gdrs_api::Value::UInt(unsigned) },
 		},
-		Err(ParseError::Ignored) => None,
-	}
+		location: parse_location(&c),
+		doc: parse_doc(&c),
+	})
+}
+
+
+
+fn parse_alias<'tu>(e: clang::Entity<'tu>, ps: &mut ParseState<'tu>) -> Result<gdrs_api::TypeAlias, ParseError> {
+	Ok(gdrs_api::TypeAlias{
+		ty: parse_type(require(e.get_typedef_underlying_type(), "underlying type")?, ps)?,
+		name: gdrs_api::ScopeName{name: require(e.get_name(), "name")?, args: Vec::with_capacity(0)},
+		location: parse_location(&e),
+	})
 }
 
 
 
-fn parse_class<'tu>(e: clang::Entity<'tu>, loc: String, ts: &mut TemplateState<'tu>) -> Option<gdrs_api::Class> {
+fn parse_class<'tu>(e: clang::Entity<'tu>, loc: String, ps: &mut ParseState<'tu>) -> Option<gdrs_api::Class> {
+	// Taken before anything can return, so a layout meant for this class never
+	// leaks into the next one
+	let layout = ps.cur_layout.take().or_else(|| e.get_type());
 	if !e.is_definition() || e.is_in_system_header() {
 		return None;
 	}
 
 	let mut class = gdrs_api::Class{
 		include: loc.clone(),
+		location: parse_location(&e),
+		doc: parse_doc(&e),
 		name: gdrs_api::ScopeName{name: e.get_name().unwrap_or_else(|| "auto".to_string()), args: Vec::with_capacity(0)},
-		inherits: None,
-		is_pod: e.get_type().map(|t| t.is_pod()).unwrap_or(false),
+		bases: Vec::with_capacity(0),
+		is_pod: layout.map(|t| t.is_pod()).unwrap_or(false),
 		is_union: e.get_kind() == clang::EntityKind::UnionDecl,
+		size: layout.and_then(|t| t.get_sizeof().ok()),
+		align: layout.and_then(|t| t.get_alignof().ok()),
 		enums: Vec::with_capacity(0),
 		aliases: Vec::with_capacity(0),
 		fields: Vec::with_capacity(0),
@@ -325,187 +1138,267 @@ fn parse_class<'tu>(e: clang::Entity<'tu>, loc: String, ts: &mut TemplateState<'
 	};
 
 	e.visit_children(|c, _| {
+		if let Err(err) = parse_class_member(c, &mut class, layout, &loc, ps) {
+			ps.skip(c, err);
+		}
+
+		clang::EntityVisitResult::Continue
+	});
+
+	Some(class)
+}
+
+
+
+fn parse_class_member<'tu>(c: clang::Entity<'tu>, class: &mut gdrs_api::Class, layout: Option<clang::Type<'tu>>, loc: &str, ps: &mut ParseState<'tu>) -> Result<(), ParseError> {
+	ps.item = Some(c);
+
+	if c.get_kind() == clang::EntityKind::BaseSpecifier {
 		let access = match c.get_accessibility() {
-			Some(clang::Accessibility::Private) => {
-				if class.is_pod && c.get_kind() == clang::EntityKind::FieldDecl {
-					let _ = writeln!(io::stderr(), "WARNING: Private POD field `{:?}`: {:?}", c, e);
-					class.is_pod = false;
-				}
-				return clang::EntityVisitResult::Continue;
-			},
+			Some(clang::Accessibility::Private) => gdrs_api::Access::Private,
 			Some(clang::Accessibility::Protected) => gdrs_api::Access::Protected,
-			Some(clang::Accessibility::Public) => gdrs_api::Access::Public,
-			None => return clang::EntityVisitResult::Continue,
+			_ => gdrs_api::Access::Public,
 		};
 
-		match c.get_kind() {
-			clang::EntityKind::BaseSpecifier => {
-				if access == gdrs_api::Access::Public {
-					if class.inherits.is_some() {
-						let _ = writeln!(io::stderr(), "WARNING: Multiple inheritance `{:?}`: {:?}", c, e);
-					} else {
-						match parse_type(c.get_type().unwrap(), ts) {
-							Ok(t) => class.inherits = Some(t),
-							Err(ParseError::Unsupported) => {
-								let _ = writeln!(io::stderr(), "WARNING: Unsupported base type `{:?}`: {:?}", c, e);
-							},
-							Err(ParseError::Ignored) => (),
-						}
-					}
-				} else {
-					let _ = writeln!(io::stderr(), "WARNING: Non-public inheritance `{:?}`: {:?}", c, e);
+		let offset = parse_base_offset(c, class, layout);
+		class.bases.push(gdrs_api::Base{
+			ty: parse_type(require(c.get_type(), "type")?, ps)?,
+			access: access,
+			is_virtual: c.is_virtual_base(),
+			offset: offset,
+		});
+		return Ok(());
+	}
+
+	let access = match c.get_accessibility() {
+		Some(clang::Accessibility::Private) => {
+			if class.is_pod && c.get_kind() == clang::EntityKind::FieldDecl {
+				ps.diagnose(diagnostics::Category::PrivatePodField, Some(c), "private field in a POD class; recording the class as non-POD".to_string());
+				class.is_pod = false;
+			}
+			if c.get_kind() == clang::EntityKind::FieldDecl {
+				class.fields.push(parse_padding(c, layout, ps)?);
+			}
+			return Ok(());
+		},
+		Some(clang::Accessibility::Protected) => gdrs_api::Access::Protected,
+		Some(clang::Accessibility::Public) => gdrs_api::Access::Public,
+		None => return Ok(()),
+	};
+
+	match c.get_kind() {
+		clang::EntityKind::EnumDecl => {
+			let _enum = parse_enum(&c, ps)?;
+			if _enum.name == "auto" {
+				let gdrs_api::Enum{variants, underlying, ..} = _enum;
+				for v in variants.into_iter() {
+					class.fields.push(gdrs_api::Field{
+						name: v.name,
+						ty: gdrs_api::TypeRef{kind: underlying.clone(), is_const: true, value: Some(v.value)},
+						access: access,
+						is_static: true,
+						offset: None,
+						bit_width: None,
+						location: v.location,
+						doc: v.doc,
+					});
 				}
-			},
-			clang::EntityKind::EnumDecl => {
-				let _enum = parse_enum(&c, ts);
-				if _enum.name == "auto" {
-					let gdrs_api::Enum{variants, underlying, ..} = _enum;
-					for v in variants.into_iter() {
-						class.fields.push(gdrs_api::Field{
-							name: v.name,
-							ty: gdrs_api::TypeRef{kind: underlying.clone(), semantic: gdrs_api::TypeSemantic::Value, is_const: true, value: Some(v.value)},
-							access: access,
-							is_static: true,
-						});
+			} else {
+				class.enums.push(_enum);
+			}
+		},
+		clang::EntityKind::TypeAliasDecl | clang::EntityKind::TypedefDecl => {
+			if let Some(underlying) = require(c.get_typedef_underlying_type(), "underlying type")?.get_declaration() {
+				if underlying.get_name().is_none() {
+					match underlying.get_kind() {
+						clang::EntityKind::EnumDecl => {
+							let mut _enum = parse_enum(&underlying, ps)?;
+							_enum.name = require(c.get_name(), "name")?;
+							class.enums.push(_enum);
+						},
+						clang::EntityKind::ClassDecl | clang::EntityKind::StructDecl => {
+							if let Some(mut nested) = parse_class(underlying, loc.to_string(), ps) {
+								nested.name.name = require(c.get_name(), "name")?;
+								class.classes.push(nested);
+							}
+						},
+						_ => (),
 					}
 				} else {
-					class.enums.push(_enum);
-				}
-			},
-			clang::EntityKind::TypeAliasDecl | clang::EntityKind::TypedefDecl => {
-				if let Some(underlying) = c.get_typedef_underlying_type().unwrap().get_declaration() {
-					if underlying.get_name().is_none() {
-						match underlying.get_kind() {
-							clang::EntityKind::EnumDecl => {
-								let mut _enum = parse_enum(&underlying, ts);
-								_enum.name = c.get_name().unwrap();
-								class.enums.push(_enum);
-							},
-							clang::EntityKind::ClassDecl | clang::EntityKind::StructDecl => {
-								if let Some(mut nested) = parse_class(underlying, loc.clone(), ts) {
-									nested.name.name = c.get_name().unwrap();
-									class.classes.push(nested);
-								}
-							},
-							_ => (),
-						}
-					} else if let Some(alias) = parse_alias(c, ts) {
-						class.aliases.push(alias);
-					}
+					class.aliases.push(parse_alias(c, ps)?);
 				}
-			},
-			clang::EntityKind::FieldDecl | clang::EntityKind::VarDecl => {
-				if c.get_type().unwrap().is_const_qualified() {
-					if let Some(val) = c.get_child(0).and_then(|exp| parse_value(exp)) {
-						let mut ty = parse_type(c.get_type().unwrap(), ts).or_else(|_| parse_type(c.get_child(0).unwrap().get_type().unwrap(), ts)).unwrap();
-						ty.value = Some(val);
-						class.fields.push(gdrs_api::Field{
-							ty: ty,
-							name: c.get_name().unwrap(),
-							access: access,
-							is_static: c.get_storage_class() == Some(clang::StorageClass::Static),
-						})
-					}
-				} else {
-					let ty = match parse_type(c.get_type().unwrap(), ts) {
-						Ok(ty) => ty,
-						Err(ParseError::Unsupported) => {
-							let _ = writeln!(io::stderr(), "WARNING: Unsupported field type `{:?}`: {:?}", c.get_type().unwrap(), c);
-							return clang::EntityVisitResult::Continue;
-						},
-						Err(ParseError::Ignored) => return clang::EntityVisitResult::Continue,
-					};
-
+			}
+		},
+		clang::EntityKind::FieldDecl | clang::EntityKind::VarDecl => {
+			let ty = require(c.get_type(), "type")?;
+			let name = require(c.get_name(), "name")?;
+
+			if ty.is_const_qualified() {
+				let init = value_children(c).pop();
+				if let Some(val) = init.and_then(|exp| parse_value(exp, ps)) {
+					let mut ty = parse_type(ty, ps).or_else(|_| parse_type(require(init.and_then(|i| i.get_type()), "initializer type")?, ps))?;
+					ty.value = Some(val);
 					class.fields.push(gdrs_api::Field{
-						name: c.get_name().unwrap(),
 						ty: ty,
+						name: name,
 						access: access,
 						is_static: c.get_storage_class() == Some(clang::StorageClass::Static),
-					});
-				}
-			},
-			clang::EntityKind::Constructor => {
-				if let Some(ctor) = parse_function(c, ts) {
-					class.ctors.push(ctor);
-				}
-			},
-			clang::EntityKind::Method => {
-				if let Some(method) = parse_function(c, ts) {
-					class.methods.push(method);
-				}
-			},
-			clang::EntityKind::Destructor => {
-				if c.is_virtual_method() {
-					class.virtual_dtor = true;
+						offset: parse_field_offset(c, layout),
+						bit_width: c.get_bit_field_width(),
+						location: parse_location(&c),
+						doc: parse_doc(&c),
+					})
 				}
-			},
-			clang::EntityKind::ClassDecl | clang::EntityKind::StructDecl => {
-				if c.get_template().is_none() {
-					if let Some(nested) = parse_class(c, loc.clone(), ts) {
-						if nested.name.name != "auto" {
-							class.classes.push(nested);
-						}
+			} else {
+				class.fields.push(gdrs_api::Field{
+					name: name,
+					ty: parse_type(ty, ps)?,
+					access: access,
+					is_static: c.get_storage_class() == Some(clang::StorageClass::Static),
+					offset: parse_field_offset(c, layout),
+					bit_width: c.get_bit_field_width(),
+					location: parse_location(&c),
+					doc: parse_doc(&c),
+				});
+			}
+		},
+		clang::EntityKind::Constructor => {
+			class.ctors.push(parse_function(c, ps)?);
+		},
+		clang::EntityKind::Method => {
+			class.methods.push(parse_function(c, ps)?);
+		},
+		clang::EntityKind::Destructor => {
+			if c.is_virtual_method() {
+				class.virtual_dtor = true;
+			}
+		},
+		clang::EntityKind::ClassDecl | clang::EntityKind::StructDecl => {
+			if c.get_template().is_none() {
+				if let Some(nested) = parse_class(c, loc.to_string(), ps) {
+					if nested.name.name != "auto" {
+						class.classes.push(nested);
 					}
 				}
-			},
-			clang::EntityKind::UnionDecl => {
-				if let Some(union) = parse_class(c, loc.to_string(), ts) {
-					if union.name.name != "auto" {
-						class.classes.push(union);
-					} else {
-						class.anon_unions.push(union);
+			}
+		},
+		clang::EntityKind::UnionDecl => {
+			if let Some(mut union) = parse_class(c, loc.to_string(), ps) {
+				if union.name.name != "auto" {
+					class.classes.push(union);
+				} else {
+					// Offsets of anonymous union members are relative to the enclosing class
+					for field in union.fields.iter_mut().filter(|f| !f.is_static && !f.name.is_empty()) {
+						field.offset = layout.and_then(|t| t.get_offsetof(&field.name).ok()).or(field.offset);
 					}
+					class.anon_unions.push(union);
 				}
-			},
-			_ => (),
-		}
+			}
+		},
+		_ => (),
+	}
 
-		clang::EntityVisitResult::Continue
-	});
+	Ok(())
+}
 
-	Some(class)
+
+
+/// Private fields are kept as anonymous padding so the recorded layout still
+/// adds up. Where the size is known they become a byte array, except for
+/// bit-fields, which keep their type and width.
+fn parse_padding<'tu>(c: clang::Entity<'tu>, layout: Option<clang::Type<'tu>>, ps: &mut ParseState<'tu>) -> Result<gdrs_api::Field, ParseError> {
+	let ty = require(c.get_type(), "type")?;
+	let bit_width = c.get_bit_field_width();
+	let ty = match ty.get_sizeof() {
+		Ok(size) if bit_width.is_none() => gdrs_api::TypeRef{
+			kind: gdrs_api::TypeKind::Array(Some(size), Box::new(gdrs_api::TypeRef{kind: gdrs_api::TypeKind::UChar, is_const: false, value: None})),
+			is_const: false,
+			value: None,
+		},
+		// Dependent types in a template pattern only have a size once substituted
+		_ => parse_type(ty, ps)?,
+	};
+
+	Ok(gdrs_api::Field{
+		name: String::new(),
+		ty: ty,
+		access: gdrs_api::Access::Private,
+		is_static: false,
+		offset: parse_field_offset(c, layout),
+		bit_width: bit_width,
+		location: parse_location(&c),
+		doc: None,
+	})
 }
 
 
 
-fn parse_function<'tu>(e: clang::Entity<'tu>, ts: &mut TemplateState<'tu>) -> Option<gdrs_api::Function> {
-	let ty = e.get_type().unwrap();
-	let result = ty.get_result_type().unwrap();
+/// Bit offset of a non-static field, looked up on the enclosing class type
+/// when the field itself belongs to a template pattern.
+fn parse_field_offset(c: clang::Entity, layout: Option<clang::Type>) -> Option<usize> {
+	if c.get_kind() != clang::EntityKind::FieldDecl {
+		return None;
+	}
 
-	Some(gdrs_api::Function{
-		name: e.get_name().unwrap(),
-		params: {
-			if let Some(params) = e.get_arguments()
-				.map(|vp| vp.into_iter().map(|p| (parse_type(p.get_type().unwrap(), ts), p.get_name().unwrap_or_else(|| "".to_string()), p.get_child(0)))
-				.collect::<Vec<_>>())
-			{
-				if let Some(i) = params.iter().position(|&(ref p, _, _)| p.is_err()) {
-					let param = e.get_arguments().unwrap()[i];
-					if params[i].0.as_ref().unwrap_err() == &ParseError::Unsupported {
-						let _ = writeln!(io::stderr(), "WARNING: Unsupported param type `{:?}`: {:?}", param, e);
-					}
-					return None;
-				}
+	c.get_offset_of_field().ok().or_else(|| c.get_name().and_then(|n| layout.and_then(|t| t.get_offsetof(n).ok())))
+}
 
-				params.into_iter().map(|(p, n, d)| {
-					let mut ty = p.unwrap();
-					ty.value = d.and_then(|d| parse_value(d));
-					gdrs_api::Var{ty: ty, name: n}
-				}).collect()
-			} else {
-				Vec::with_capacity(0)
-			}
-		},
-		return_ty: if result.get_kind() == clang::TypeKind::Void { None } else {
-			match parse_type(result, ts) {
-				Ok(r) => Some(r),
-				Err(ParseError::Unsupported) => {
-					let _ = writeln!(io::stderr(), "WARNING: Unsupported return type `{:?}`: {:?}", result, e);
-					return None;
-				},
+
+
+/// Bit offset of the base `c` in the class of type `layout`. libclang can't
+/// report it, but the first non-virtual base starts the class, unless the
+/// class needs a vtable pointer there and the base doesn't provide one.
+fn parse_base_offset(c: clang::Entity, class: &gdrs_api::Class, layout: Option<clang::Type>) -> Option<usize> {
+	if c.is_virtual_base() || class.bases.iter().any(|b| !b.is_virtual) {
+		return None;
+	}
+
+	let base = c.get_type().and_then(|t| t.get_declaration()).and_then(|d| d.get_definition());
+	let derived = layout.and_then(|t| t.get_declaration()).and_then(|d| d.get_definition());
+	match (base.and_then(is_dynamic), derived.and_then(is_dynamic)) {
+		(Some(true), _) | (Some(false), Some(false)) => Some(0),
+		_ => None,
+	}
+}
+
+
+
+/// Whether the class `e` has a vtable pointer. `None` if one of its bases
+/// can't be resolved, as in a template pattern.
+fn is_dynamic(e: clang::Entity) -> Option<bool> {
+	let mut dynamic = false;
+	for c in e.get_children().into_iter() {
+		dynamic |= match c.get_kind() {
+			clang::EntityKind::Method | clang::EntityKind::Destructor => c.is_virtual_method(),
+			clang::EntityKind::BaseSpecifier if c.is_virtual_base() => true,
+			clang::EntityKind::BaseSpecifier => match c.get_type().and_then(|t| t.get_declaration()).and_then(|d| d.get_definition()).map(is_dynamic) {
+				Some(Some(dynamic)) => dynamic,
 				_ => return None,
-			}
-		},
+			},
+			_ => false,
+		};
+	}
+
+	Some(dynamic)
+}
+
+
+
+fn parse_function<'tu>(e: clang::Entity<'tu>, ps: &mut ParseState<'tu>) -> Result<gdrs_api::Function, ParseError> {
+	let ty = require(e.get_type(), "type")?;
+	let result = require(ty.get_result_type(), "result type")?;
+
+	let mut params = Vec::new();
+	for p in e.get_arguments().unwrap_or_else(Vec::new).into_iter() {
+		let mut ty = parse_type(require(p.get_type(), "parameter type")?, ps)?;
+		ty.value = default_argument(p).and_then(|d| parse_value(d, ps));
+		params.push(gdrs_api::Var{ty: ty, name: p.get_name().unwrap_or_else(|| "".to_string()), location: parse_location(&p), doc: None});
+	}
+
+	Ok(gdrs_api::Function{
+		name: require(e.get_name(), "name")?,
+		params: params,
+		return_ty: if result.get_kind() == clang::TypeKind::Void { None } else { Some(parse_type(result, ps)?) },
 		semantic: if e.is_virtual_method() {
 			gdrs_api::FunctionSemantic::Virtual
 		} else if e.is_static_method() {
@@ -517,60 +1410,79 @@ fn parse_function<'tu>(e: clang::Entity<'tu>, ts: &mut TemplateState<'tu>) -> Op
 		},
 		access: if let Some(clang::Accessibility::Protected) = e.get_accessibility() { gdrs_api::Access::Protected } else { gdrs_api::Access::Public },
 		is_const: e.is_const_method(),
+		location: parse_location(&e),
+		doc: parse_doc(&e),
 	})
 }
 
 
 
-fn parse_type<'tu>(mut t: clang::Type, ts: &mut TemplateState<'tu>) -> Result<gdrs_api::TypeRef, ParseError> {
-	t = t.get_elaborated_type().unwrap_or(t);
-
-	let semantic = match t.get_kind() {
-		clang::TypeKind::Pointer | clang::TypeKind::IncompleteArray => {
-			t = t.get_pointee_type().or_else(|| t.get_element_type()).map(|t| t.get_elaborated_type().unwrap_or(t)).unwrap();
-			if t.get_kind() == clang::TypeKind::Pointer {
-				t = t.get_pointee_type().map(|t| t.get_elaborated_type().unwrap_or(t)).unwrap();
-				gdrs_api::TypeSemantic::PointerToPointer
-			} else {
-				gdrs_api::TypeSemantic::Pointer
-			}
-		},
-		clang::TypeKind::LValueReference => {
-			t = t.get_pointee_type().map(|t| t.get_elaborated_type().unwrap_or(t)).unwrap();
-			if t.get_kind() == clang::TypeKind::Pointer {
-				t = t.get_pointee_type().map(|t| t.get_elaborated_type().unwrap_or(t)).unwrap();
-				gdrs_api::TypeSemantic::ReferenceToPointer
-			} else {
-				gdrs_api::TypeSemantic::Reference
-			}
+/// Parses a type, falling back to `TypeKind::Opaque` for types that can't be
+/// represented so the items using them are kept. Only types that should be
+/// skipped outright, such as `auto`, are errors.
+fn parse_type<'tu>(t: clang::Type<'tu>, ps: &mut ParseState<'tu>) -> Result<gdrs_api::TypeRef, ParseError> {
+	match parse_exact_type(t, ps) {
+		Err(ParseError::Unsupported(reason)) => {
+			ps.diagnose(diagnostics::Category::UnsupportedType, None, format!("`{}` recorded as opaque: {}", t.get_display_name(), reason));
+			Ok(parse_opaque_type(t))
 		},
-		clang::TypeKind::ConstantArray => {
-			let size = t.get_size().unwrap();
-			t = t.get_element_type().map(|t| t.get_elaborated_type().unwrap_or(t)).unwrap();
-			match t.get_kind() {
-				clang::TypeKind::ConstantArray => {
-					let size1 = t.get_size().unwrap();
-					t = t.get_element_type().map(|t| t.get_elaborated_type().unwrap_or(t)).unwrap();
-					gdrs_api::TypeSemantic::ArrayOfArray(size, size1)
-				},
-				clang::TypeKind::Pointer => {
-					t = t.get_pointee_type().map(|t| t.get_elaborated_type().unwrap_or(t)).unwrap();
-					gdrs_api::TypeSemantic::ArrayOfPointer(size)
-				},
-				_ => gdrs_api::TypeSemantic::Array(size),
-			}
+		r => r,
+	}
+}
+
+
+
+fn parse_opaque_type(t: clang::Type) -> gdrs_api::TypeRef {
+	let is_const = t.is_const_qualified();
+	let spelling = t.get_display_name();
+
+	gdrs_api::TypeRef{
+		kind: gdrs_api::TypeKind::Opaque{
+			spelling: if is_const && spelling.starts_with("const ") { spelling[6..].to_string() } else { spelling },
+			size: t.get_sizeof().ok(),
+			align: t.get_alignof().ok(),
 		},
-		_ => gdrs_api::TypeSemantic::Value,
-	};
+		is_const: is_const,
+		value: None,
+	}
+}
+
+
+
+fn parse_exact_type<'tu>(mut t: clang::Type<'tu>, ps: &mut ParseState<'tu>) -> Result<gdrs_api::TypeRef, ParseError> {
+	t = t.get_elaborated_type().unwrap_or(t);
 
 	Ok(gdrs_api::TypeRef{
 		kind: match t.get_kind() {
-			clang::TypeKind::Auto
-			| clang::TypeKind::Unexposed
-			| clang::TypeKind::BlockPointer
-			| clang::TypeKind::MemberPointer
-			=> return Err(ParseError::Ignored),
+			clang::TypeKind::Unexposed if t.get_declaration().map(|d| d.get_kind()) == Some(clang::EntityKind::TemplateTypeParameter) => {
+				let name = t.get_declaration().and_then(|d| d.get_name()).unwrap_or_else(String::new);
+				if let Some(arg) = ps.cur_args.get(&name) {
+					let mut arg = arg.clone();
+					arg.is_const = arg.is_const || t.is_const_qualified();
+					return Ok(arg);
+				}
+
+				gdrs_api::TypeKind::TemplateParam(name)
+			},
+
+			clang::TypeKind::Auto => return Err(ParseError::Ignored),
+			// Mostly dependent types and template specializations clang won't
+			// break down further
+			clang::TypeKind::Unexposed => return Err(ParseError::Unsupported("unexposed type".to_string())),
+
+			clang::TypeKind::Pointer => {
+				let pointee = require(t.get_pointee_type(), "pointee type")?;
+				match pointee.get_canonical_type().get_kind() {
+					clang::TypeKind::FunctionPrototype | clang::TypeKind::FunctionNoPrototype => parse_function_type(pointee, ps)?,
+					_ => gdrs_api::TypeKind::Pointer(Box::new(parse_type(pointee, ps)?)),
+				}
+			},
+			clang::TypeKind::LValueReference => gdrs_api::TypeKind::Reference(Box::new(parse_type(require(t.get_pointee_type(), "pointee type")?, ps)?)),
+			clang::TypeKind::RValueReference => gdrs_api::TypeKind::RValueReference(Box::new(parse_type(require(t.get_pointee_type(), "pointee type")?, ps)?)),
+			clang::TypeKind::ConstantArray => gdrs_api::TypeKind::Array(Some(require(t.get_size(), "array size")?), Box::new(parse_type(require(t.get_element_type(), "element type")?, ps)?)),
+			clang::TypeKind::IncompleteArray => gdrs_api::TypeKind::Array(None, Box::new(parse_type(require(t.get_element_type(), "element type")?, ps)?)),
 
+			clang::TypeKind::Void => gdrs_api::TypeKind::Void,
 			clang::TypeKind::Bool => gdrs_api::TypeKind::Bool,
 			clang::TypeKind::CharS | clang::TypeKind::SChar => gdrs_api::TypeKind::Char,
 			clang::TypeKind::CharU | clang::TypeKind::UChar => gdrs_api::TypeKind::UChar,
@@ -586,10 +1498,8 @@ fn parse_type<'tu>(mut t: clang::Type, ts: &mut TemplateState<'tu>) -> Result<gd
 			clang::TypeKind::Float => gdrs_api::TypeKind::Float,
 			clang::TypeKind::Double => gdrs_api::TypeKind::Double,
 
-			clang::TypeKind::Void if semantic != gdrs_api::TypeSemantic::Value => gdrs_api::TypeKind::Void,
-
 			k if k == clang::TypeKind::Typedef || k == clang::TypeKind::Enum || k == clang::TypeKind::Record => {
-				let mut p = t.get_declaration().unwrap();
+				let mut p = require(t.get_declaration(), "declaration")?;
 				let mut name_path = Vec::new();
 
 				loop {
@@ -599,52 +1509,50 @@ fn parse_type<'tu>(mut t: clang::Type, ts: &mut TemplateState<'tu>) -> Result<gd
 						clang::EntityKind::Namespace => {
 							name_path.push(gdrs_api::ScopeName{name: name, args: Vec::with_capacity(0)});
 						},
-						_ => match p.get_type().unwrap().get_kind() {
+						_ => match require(p.get_type(), "scope type")?.get_kind() {
 							clang::TypeKind::Enum | clang::TypeKind::Typedef => {
 								name_path.push(gdrs_api::ScopeName{name: name, args: Vec::with_capacity(0)});
 							},
 							clang::TypeKind::Record => {
-								if let Some(args) = p.get_type().unwrap().get_template_argument_types().map(|a| a.into_iter().map(|a| parse_type(a.unwrap(), ts)).collect::<Vec<_>>()) {
-									if let Some(i) = args.iter().position(|a| a.is_err()) {
-										match *args[i].as_ref().unwrap_err() {
-											ParseError::Unsupported => {
-												let _ = writeln!(
-													io::stderr(),
-													"WARNING: Unsupported template param type `{:?}`",
-													p.get_type().unwrap().get_template_argument_types().unwrap()[i]
-												);
-												return Err(ParseError::Unsupported);
-											},
-											ParseError::Ignored => return Err(ParseError::Ignored),
-										}
+								if let Some(args) = p.get_type().and_then(|t| t.get_template_argument_types()) {
+									let args = match args.into_iter().enumerate().map(|(i, a)| {
+										a.ok_or_else(|| ParseError::Unsupported(format!("template argument {} of `{}` is not a type", i, name)))
+											.and_then(|a| parse_type(a, ps))
+									}).collect::<Result<Vec<_>, _>>() {
+										Ok(args) => args,
+										Err(err) => {
+											// The type itself is still kept as opaque, but
+											// the specialization is never instantiated
+											if p.get_template().is_some() {
+												ps.skip(p, err.clone());
+											}
+											return Err(err);
+										},
+									};
+									if let Some(template) = p.get_template() {
+										queue_instantiation(p, template, &args, ps);
 									}
 
-									name_path.push(gdrs_api::ScopeName{name: name, args: args.into_iter().map(|a| a.unwrap()).collect()});
+									name_path.push(gdrs_api::ScopeName{name: name, args: args});
 								} else {
 									name_path.push(gdrs_api::ScopeName{name: name, args: Vec::with_capacity(0)});
 								}
 							},
-							_ => {
-								let _ = writeln!(io::stderr(), "WARNING: Unsupported scope parent: `{:?}`", p);
-								return Err(ParseError::Unsupported);
-							},
+							_ => return Err(ParseError::Unsupported(format!("unsupported enclosing scope `{}` ({:?})", name, p.get_kind()))),
 						},
 					}
 
-					p = p.get_semantic_parent().unwrap();
+					p = require(p.get_semantic_parent(), "enclosing scope")?;
 					while p.get_kind() == clang::EntityKind::UnexposedDecl && p.get_name().is_none() {
-						p = p.get_semantic_parent().unwrap();
+						p = require(p.get_semantic_parent(), "enclosing scope")?;
 					}
 				}
+				name_path.reverse();
 
 				gdrs_api::TypeKind::Elaborated(name_path)
 			},
-			k => {
-				let _ = writeln!(io::stderr(), "WARNING: Unsupported type kind `{:?}`", k);
-				return Err(ParseError::Unsupported);
-			},
+			k => return Err(ParseError::Unsupported(format!("unsupported type kind {:?}", k))),
 		},
-		semantic: semantic,
 		is_const: t.is_const_qualified(),
 		value: None,
 	})
@@ -652,9 +1560,93 @@ fn parse_type<'tu>(mut t: clang::Type, ts: &mut TemplateState<'tu>) -> Result<gd
 
 
 
-fn parse_value(expr: clang::Entity) -> Option<gdrs_api::Value> {
-	if let (Some(kind), Some(val)) = (expr.get_type().map(|t| t.get_kind()), expr.evaluate()) {
-		match val {
+fn parse_function_type<'tu>(mut t: clang::Type<'tu>, ps: &mut ParseState<'tu>) -> Result<gdrs_api::TypeKind, ParseError> {
+	// Look through typedefs by hand so parameter types keep their sugar,
+	// and only fall back to the canonical type for anything else (parens).
+	while t.get_kind() == clang::TypeKind::Typedef {
+		t = require(t.get_declaration().and_then(|d| d.get_typedef_underlying_type()), "underlying type")?;
+	}
+	if t.get_kind() != clang::TypeKind::FunctionPrototype && t.get_kind() != clang::TypeKind::FunctionNoPrototype {
+		t = t.get_canonical_type();
+	}
+
+	let result = require(t.get_result_type(), "result type")?;
+	let mut params = Vec::new();
+	for p in t.get_argument_types().unwrap_or_else(Vec::new).into_iter() {
+		params.push(parse_type(p, ps)?);
+	}
+
+	Ok(gdrs_api::TypeKind::FunctionPointer{
+		params: params,
+		return_ty: if result.get_kind() == clang::TypeKind::Void { None } else { Some(Box::new(parse_type(result, ps)?)) },
+		variadic: t.is_variadic(),
+	})
+}
+
+
+
+fn parse_location(e: &clang::Entity) -> Option<gdrs_api::SourceLocation> {
+	e.get_location().map(convert_location)
+}
+
+
+
+fn convert_location(l: clang::source::SourceLocation) -> gdrs_api::SourceLocation {
+	let l = l.get_expansion_location();
+	gdrs_api::SourceLocation{
+		file: l.file.get_path().to_string_lossy().into_owned(),
+		line: l.line,
+		column: l.column,
+	}
+}
+
+
+
+fn parse_doc(e: &clang::Entity) -> Option<gdrs_api::Doc> {
+	e.get_comment().map(|raw| gdrs_api::Doc{
+		raw: raw,
+		brief: e.get_comment_brief(),
+	})
+}
+
+
+
+/// Parses a constant initializer or default argument. Expressions that
+/// can't be evaluated are kept as their source text.
+fn parse_value<'tu>(expr: clang::Entity<'tu>, ps: &mut ParseState<'tu>) -> Option<gdrs_api::Value> {
+	let kind = expr.get_type().map(|t| t.get_canonical_type().get_kind());
+
+	match expr.get_kind() {
+		clang::EntityKind::NullPtrLiteralExpr => return Some(gdrs_api::Value::Null),
+		clang::EntityKind::DeclRefExpr => if let Some(v) = expr.get_reference().and_then(parse_enum_variant_ref) {
+			return Some(v);
+		},
+		clang::EntityKind::CallExpr => return parse_construct(expr, ps).or_else(|| parse_raw(expr)),
+		// Temporaries and parentheses around the interesting part; conversions
+		// change the type, and are left to evaluation below
+		clang::EntityKind::UnexposedExpr | clang::EntityKind::ParenExpr => {
+			let children = value_children(expr);
+			if children.len() == 1 && children[0].get_type().map(|t| t.get_canonical_type()) == expr.get_type().map(|t| t.get_canonical_type()) {
+				return parse_value(children[0], ps);
+			}
+		},
+		_ => (),
+	}
+
+	if kind == Some(clang::TypeKind::Pointer) {
+		return match expr.evaluate() {
+			Some(clang::EvaluationResult::Integer(0)) => Some(gdrs_api::Value::Null),
+			Some(clang::EvaluationResult::String(s)) => Some(gdrs_api::Value::String(s.to_string_lossy().into_owned())),
+			_ => match parse_raw(expr) {
+				Some(gdrs_api::Value::Raw(ref raw)) if is_null_spelling(raw) => Some(gdrs_api::Value::Null),
+				raw => raw,
+			},
+		};
+	}
+
+	match (kind, expr.evaluate()) {
+		(Some(kind), Some(val)) => match val {
+			clang::EvaluationResult::Integer(i) if kind == clang::TypeKind::Bool => Some(gdrs_api::Value::Bool(i != 0)),
 			clang::EvaluationResult::Integer(i)
 				if kind == clang::TypeKind::CharU
 				|| kind == clang::TypeKind::UChar
@@ -662,7 +1654,6 @@ fn parse_value(expr: clang::Entity) -> Option<gdrs_api::Value> {
 				|| kind == clang::TypeKind::UInt
 				|| kind == clang::TypeKind::ULong
 				|| kind == clang::TypeKind::ULongLong
-				|| kind == clang::TypeKind::Bool
 			=> Some(gdrs_api::Value::UInt(i as u64)),
 			clang::EvaluationResult::Integer(i)
 				if kind == clang::TypeKind::CharS
@@ -676,12 +1667,94 @@ fn parse_value(expr: clang::Entity) -> Option<gdrs_api::Value> {
 			clang::EvaluationResult::Float(d) if kind == clang::TypeKind::Float => Some(gdrs_api::Value::Float(d as f32)),
 			clang::EvaluationResult::Float(d) if kind == clang::TypeKind::Double => Some(gdrs_api::Value::Double(d)),
 			clang::EvaluationResult::String(s) => Some(gdrs_api::Value::String(s.to_string_lossy().into_owned())),
-			v => {
-				let _ = writeln!(io::stderr(), "WARNING: Unsupported evaluation result `{:?}`: {:?}", v, expr);
-				return None;
-			},
+			_ => parse_raw(expr),
+		},
+		_ => parse_raw(expr),
+	}
+}
+
+
+
+/// A constructor call such as `Vector2()` or `Color(1, 1, 1)`. Calls to
+/// ordinary functions aren't constant, and are left to the caller.
+fn parse_construct<'tu>(expr: clang::Entity<'tu>, ps: &mut ParseState<'tu>) -> Option<gdrs_api::Value> {
+	if let Some(callee) = expr.get_reference() {
+		if callee.get_kind() != clang::EntityKind::Constructor {
+			return None;
 		}
-	} else {
-		None
 	}
+
+	let ty = match expr.get_type().map(|t| parse_type(t, ps)) {
+		Some(Ok(ty)) => ty,
+		_ => return None,
+	};
+	let mut args = Vec::new();
+	for a in value_children(expr) {
+		match parse_value(a, ps) {
+			Some(v) => args.push(v),
+			None => return None,
+		}
+	}
+
+	// Passing a temporary by value shows up as an elidable copy of it
+	if args.len() == 1 {
+		if let gdrs_api::Value::Construct{ty: ref inner, ..} = args[0] {
+			if inner.same_type(&ty) {
+				return args.pop();
+			}
+		}
+	}
+
+	Some(gdrs_api::Value::Construct{ty: Box::new(ty), args: args})
+}
+
+
+
+fn parse_enum_variant_ref(e: clang::Entity) -> Option<gdrs_api::Value> {
+	if e.get_kind() != clang::EntityKind::EnumConstantDecl {
+		return None;
+	}
+
+	e.get_name().map(|name| gdrs_api::Value::EnumVariant{
+		path: e.get_semantic_parent().map(entity_path).unwrap_or_else(Vec::new),
+		name: name,
+	})
+}
+
+
+
+fn parse_raw(expr: clang::Entity) -> Option<gdrs_api::Value> {
+	expr.get_range()
+		.map(|r| r.tokenize().iter().map(|t| t.get_spelling()).collect::<Vec<_>>().join(" "))
+		.map(gdrs_api::Value::Raw)
+}
+
+
+
+fn is_null_spelling(raw: &str) -> bool {
+	match raw.trim() {
+		"NULL" | "nullptr" | "0" | "__null" => true,
+		_ => false,
+	}
+}
+
+
+
+/// The default argument of a parameter. Parameters without an `=` can still
+/// have expression children, such as an array size.
+fn default_argument(p: clang::Entity) -> Option<clang::Entity> {
+	let has_default = p.get_range().map(|r| r.tokenize().iter().any(|t| t.get_spelling() == "=")).unwrap_or(false);
+	if has_default { value_children(p).pop() } else { None }
+}
+
+
+
+/// Children of an expression or declaration that are themselves
+/// expressions, skipping the references that name its type and the
+/// parameters of function pointer types.
+fn value_children(e: clang::Entity) -> Vec<clang::Entity> {
+	e.get_children().into_iter().filter(|c| match c.get_kind() {
+		clang::EntityKind::TypeRef | clang::EntityKind::TemplateRef | clang::EntityKind::NamespaceRef | clang::EntityKind::ParmDecl => false,
+		_ => true,
+	}).collect()
 }
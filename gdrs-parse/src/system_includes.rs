@@ -0,0 +1,58 @@
+//! Finds the system include directories of a C++ compiler, so headers
+//! resolve the same way they do when the engine is built with it.
+
+use std::process::{Command, Stdio};
+
+use compile_commands::split_command;
+
+
+
+const SEARCH_START: &'static str = "#include <...> search starts here:";
+const SEARCH_END: &'static str = "End of search list.";
+
+
+
+/// The `#include <...>` search list of `cxx`, a command line such as
+/// `ccache g++` or `clang++ -m32`. It is detected on every run: the command
+/// alone doesn't tell which compiler ends up running behind a wrapper.
+pub fn detect(cxx: &str) -> Result<Vec<String>, String> {
+	let command = split_command(cxx);
+	let (program, args) = match command.split_first() {
+		Some((program, args)) => (program, args),
+		None => return Err("no compiler given".to_string()),
+	};
+
+	info!("Detecting system include directories of `{}`", cxx);
+	run(program, args)
+}
+
+
+
+/// Preprocesses an empty file with `program -v` and reads the search list
+/// from what it prints to stderr.
+fn run(program: &str, args: &[String]) -> Result<Vec<String>, String> {
+	let output = Command::new(program)
+		.args(args)
+		.args(&["-x", "c++", "-v", "-E", "-"])
+		.stdin(Stdio::null())
+		.stdout(Stdio::null())
+		.stderr(Stdio::piped())
+		.output()
+		.map_err(|e| format!("can't run `{}`: {}", program, e))?;
+	if !output.status.success() {
+		return Err(format!("`{}` failed with {}", program, output.status));
+	}
+
+	let stderr = String::from_utf8_lossy(&output.stderr);
+	let mut lines = stderr.lines().skip_while(|l| l.trim() != SEARCH_START);
+	if lines.next().is_none() {
+		return Err(format!("`{}` printed no include search list", program));
+	}
+
+	Ok(lines
+		.take_while(|l| l.trim() != SEARCH_END)
+		// Darwin marks framework directories, which aren't include paths
+		.filter(|l| !l.ends_with("(framework directory)"))
+		.map(|l| l.trim().to_string())
+		.collect())
+}
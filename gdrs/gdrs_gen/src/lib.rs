@@ -1,2 +1,21 @@
+extern crate gdrs_api;
+
+use std::fs;
+use std::io;
+use std::path::Path;
+
+
+
 pub fn generate_api() {
 }
+
+
+
+/// Loads an API description written by gdrs-parse, in binary or JSON
+/// depending on the extension, rejecting documents from an incompatible
+/// schema version.
+pub fn load_api<P: AsRef<Path>>(path: P) -> Result<gdrs_api::ApiDocument, gdrs_api::io::Error> {
+	let format = gdrs_api::io::Format::from_path(&path);
+	let file = fs::File::open(path)?;
+	gdrs_api::io::read(io::BufReader::new(file), format)
+}